@@ -0,0 +1,179 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AsmError {
+    // Line numbers count from 1
+    UnknownMnemonic { line: usize },
+    // The mnemonic exists, but not with these operands
+    BadOperands { line: usize },
+    // A number too big for the field it's encoded into
+    OutOfRange { line: usize },
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsmError::UnknownMnemonic { line } => write!(f, "line {}: unknown mnemonic", line),
+            AsmError::BadOperands { line } => write!(f, "line {}: bad operands", line),
+            AsmError::OutOfRange { line } => write!(f, "line {}: value out of range", line),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AsmError {}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    V(u16),
+    Num(u16),
+    I,
+    IndirectI,
+    DT,
+    ST,
+    K,
+    F,
+    HF,
+    B,
+    R,
+    Long,
+}
+
+// Assembles the mnemonics disassemble prints, one instruction per line,
+// e.g. "LD VA, 0x02". Registers can be written VA or V10, numbers in hex
+// with 0x or in decimal. Anything after a ';' is a comment. There are no
+// labels; jumps take absolute addresses.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut out = Vec::new();
+    for (idx, text) in source.lines().enumerate() {
+        let line = idx + 1;
+        let text = text.split(';').next().unwrap_or("").trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, rest) = match text.split_once(char::is_whitespace) {
+            Some((mnemonic, rest)) => (mnemonic, rest.trim()),
+            None => (text, ""),
+        };
+        let mut operands = Vec::new();
+        if !rest.is_empty() {
+            for operand in rest.split(',') {
+                let operand = parse_operand(operand.trim()).ok_or(AsmError::BadOperands { line })?;
+                operands.push(operand);
+            }
+        }
+
+        let op = encode(&mnemonic.to_ascii_uppercase(), &operands, line)?;
+        out.push((op >> 8) as u8);
+        out.push((op & 0xFF) as u8);
+    }
+    return Ok(out);
+}
+
+fn parse_operand(text: &str) -> Option<Operand> {
+    let upper = text.to_ascii_uppercase();
+    let operand = match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DT,
+        "ST" => Operand::ST,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "HF" => Operand::HF,
+        "B" => Operand::B,
+        "R" => Operand::R,
+        "LONG" => Operand::Long,
+        _ => {
+            if let Some(reg) = upper.strip_prefix('V') {
+                // A single hex digit (VA) or decimal like disassemble prints (V10)
+                let num = if reg.len() == 1 {
+                    u16::from_str_radix(reg, 16).ok()?
+                } else {
+                    reg.parse::<u16>().ok()?
+                };
+                if num > 0xF {
+                    return None;
+                }
+                Operand::V(num)
+            } else if let Some(hex) = upper.strip_prefix("0X") {
+                Operand::Num(u16::from_str_radix(hex, 16).ok()?)
+            } else {
+                Operand::Num(upper.parse::<u16>().ok()?)
+            }
+        },
+    };
+    return Some(operand);
+}
+
+fn encode(mnemonic: &str, operands: &[Operand], line: usize) -> Result<u16, AsmError> {
+    use Operand::*;
+
+    // Checks a number fits in `max` before it's placed into the opcode
+    let fit = |n: u16, max: u16| if n <= max { Ok(n) } else { Err(AsmError::OutOfRange { line }) };
+
+    let op = match (mnemonic, operands) {
+        ("NOP", []) => 0x0000,
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SCR", []) => 0x00FB,
+        ("SCL", []) => 0x00FC,
+        ("EXIT", []) => 0x00FD,
+        ("LOW", []) => 0x00FE,
+        ("HIGH", []) => 0x00FF,
+        ("AUDIO", []) => 0xF002,
+        ("SCD", [Num(n)]) => 0x00C0 | fit(*n, 0xF)?,
+        ("SCU", [Num(n)]) => 0x00D0 | fit(*n, 0xF)?,
+        ("JP", [Num(nnn)]) => 0x1000 | fit(*nnn, 0xFFF)?,
+        ("JP", [V(0), Num(nnn)]) => 0xB000 | fit(*nnn, 0xFFF)?,
+        ("CALL", [Num(nnn)]) => 0x2000 | fit(*nnn, 0xFFF)?,
+        ("SE", [V(x), Num(nn)]) => 0x3000 | x << 8 | fit(*nn, 0xFF)?,
+        ("SNE", [V(x), Num(nn)]) => 0x4000 | x << 8 | fit(*nn, 0xFF)?,
+        ("SE", [V(x), V(y)]) => 0x5000 | x << 8 | y << 4,
+        ("SNE", [V(x), V(y)]) => 0x9000 | x << 8 | y << 4,
+        ("LD", [V(x), Num(nn)]) => 0x6000 | x << 8 | fit(*nn, 0xFF)?,
+        ("ADD", [V(x), Num(nn)]) => 0x7000 | x << 8 | fit(*nn, 0xFF)?,
+        ("LD", [V(x), V(y)]) => 0x8000 | x << 8 | y << 4,
+        ("OR", [V(x), V(y)]) => 0x8001 | x << 8 | y << 4,
+        ("AND", [V(x), V(y)]) => 0x8002 | x << 8 | y << 4,
+        ("XOR", [V(x), V(y)]) => 0x8003 | x << 8 | y << 4,
+        ("ADD", [V(x), V(y)]) => 0x8004 | x << 8 | y << 4,
+        ("SUB", [V(x), V(y)]) => 0x8005 | x << 8 | y << 4,
+        ("SHR", [V(x), V(y)]) => 0x8006 | x << 8 | y << 4,
+        ("SHR", [V(x)]) => 0x8006 | x << 8 | x << 4,
+        ("SUBN", [V(x), V(y)]) => 0x8007 | x << 8 | y << 4,
+        ("SHL", [V(x), V(y)]) => 0x800E | x << 8 | y << 4,
+        ("SHL", [V(x)]) => 0x800E | x << 8 | x << 4,
+        ("LD", [I, Num(nnn)]) => 0xA000 | fit(*nnn, 0xFFF)?,
+        // Follow with a DW holding the address
+        ("LD", [I, Long]) => 0xF000,
+        ("RND", [V(x), Num(nn)]) => 0xC000 | x << 8 | fit(*nn, 0xFF)?,
+        ("DRW", [V(x), V(y), Num(n)]) => 0xD000 | x << 8 | y << 4 | fit(*n, 0xF)?,
+        ("SKP", [V(x)]) => 0xE09E | x << 8,
+        ("SKNP", [V(x)]) => 0xE0A1 | x << 8,
+        ("PLANE", [Num(n)]) => 0xF001 | fit(*n, 0xF)? << 8,
+        ("LD", [V(x), DT]) => 0xF007 | x << 8,
+        ("LD", [V(x), K]) => 0xF00A | x << 8,
+        ("LD", [DT, V(x)]) => 0xF015 | x << 8,
+        ("LD", [ST, V(x)]) => 0xF018 | x << 8,
+        ("ADD", [I, V(x)]) => 0xF01E | x << 8,
+        ("LD", [F, V(x)]) => 0xF029 | x << 8,
+        ("LD", [HF, V(x)]) => 0xF030 | x << 8,
+        ("LD", [B, V(x)]) => 0xF033 | x << 8,
+        ("PITCH", [V(x)]) => 0xF03A | x << 8,
+        ("LD", [IndirectI, V(x)]) => 0xF055 | x << 8,
+        ("LD", [V(x), IndirectI]) => 0xF065 | x << 8,
+        ("LD", [R, V(x)]) => 0xF075 | x << 8,
+        ("LD", [V(x), R]) => 0xF085 | x << 8,
+        ("DW", [Num(word)]) => *word,
+        (
+            "NOP" | "CLS" | "RET" | "SCR" | "SCL" | "EXIT" | "LOW" | "HIGH" | "AUDIO" | "SCD" | "SCU"
+            | "JP" | "CALL" | "SE" | "SNE" | "LD" | "ADD" | "OR" | "AND" | "XOR" | "SUB" | "SHR"
+            | "SUBN" | "SHL" | "RND" | "DRW" | "SKP" | "SKNP" | "PLANE" | "PITCH" | "DW",
+            _,
+        ) => return Err(AsmError::BadOperands { line }),
+        _ => return Err(AsmError::UnknownMnemonic { line }),
+    };
+    return Ok(op);
+}
@@ -0,0 +1,83 @@
+use crate::rng::Random;
+use crate::{Emulator, LfsrRng, QuirkConfig};
+use crate::{DEFAULT_CLOCK_HZ, RAM_SIZE, START_ADDR};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+// Collects the construction-time options in one place, e.g.
+// EmulatorBuilder::new().quirks(q).seed(1).build(). Anything left unset
+// gets the same value Emulator::new() would use.
+#[derive(Clone, Debug)]
+pub struct EmulatorBuilder {
+    quirks: QuirkConfig,
+    clock_hz: u32,
+    seed: Option<u64>,
+    lfsr_rng: bool,
+    ram_size: usize,
+    start_addr: u16,
+}
+
+impl EmulatorBuilder {
+    pub fn new() -> Self {
+        return Self {
+            quirks: QuirkConfig::default(),
+            clock_hz: DEFAULT_CLOCK_HZ,
+            seed: None,
+            lfsr_rng: false,
+            ram_size: RAM_SIZE,
+            start_addr: START_ADDR,
+        };
+    }
+
+    pub fn quirks(mut self, quirks: QuirkConfig) -> Self {
+        self.quirks = quirks;
+        return self;
+    }
+
+    pub fn clock_hz(mut self, hz: u32) -> Self {
+        self.clock_hz = hz;
+        return self;
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        return self;
+    }
+
+    // CXNN uses an LfsrRng, seeded with the low 16 bits of `seed` if one is set
+    pub fn lfsr_rng(mut self) -> Self {
+        self.lfsr_rng = true;
+        return self;
+    }
+
+    pub fn ram_size(mut self, bytes: usize) -> Self {
+        self.ram_size = bytes;
+        return self;
+    }
+
+    pub fn start_addr(mut self, addr: u16) -> Self {
+        self.start_addr = addr;
+        return self;
+    }
+
+    // Panics on the same bad RAM sizes and start addresses as
+    // Emulator::with_ram_size and Emulator::with_start_addr
+    pub fn build(self) -> Emulator {
+        let mut emulator = Emulator::with_quirks(self.quirks);
+        emulator.resize_ram(self.ram_size);
+        emulator.set_start_addr(self.start_addr);
+        emulator.set_clock_hz(self.clock_hz);
+        if self.lfsr_rng {
+            emulator.set_rng(Random::Lfsr(LfsrRng::new(self.seed.unwrap_or(0) as u16)));
+        } else if let Some(seed) = self.seed {
+            emulator.set_rng(Random::Std(StdRng::seed_from_u64(seed)));
+        }
+        return emulator;
+    }
+}
+
+impl Default for EmulatorBuilder {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
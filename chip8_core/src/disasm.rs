@@ -0,0 +1,61 @@
+use crate::instruction::{decode, Instruction};
+use alloc::format;
+use alloc::string::String;
+
+// Renders an opcode in the usual Cowgod-style mnemonics, e.g. 0x6A02 -> "LD V10, 0x02".
+// Anything the emulator doesn't execute comes out as a raw data word, "DW 0x____".
+pub fn disassemble(op: u16) -> String {
+    match decode(op) {
+        Instruction::Nop => String::from("NOP"),
+        Instruction::ScrollDown { n } => format!("SCD {}", n),
+        Instruction::ScrollUp { n } => format!("SCU {}", n),
+        Instruction::Cls => String::from("CLS"),
+        Instruction::Ret => String::from("RET"),
+        Instruction::ScrollRight => String::from("SCR"),
+        Instruction::ScrollLeft => String::from("SCL"),
+        Instruction::Exit => String::from("EXIT"),
+        Instruction::LowRes => String::from("LOW"),
+        Instruction::HighRes => String::from("HIGH"),
+        Instruction::Jump { nnn } => format!("JP 0x{:03X}", nnn),
+        Instruction::Call { nnn } => format!("CALL 0x{:03X}", nnn),
+        Instruction::SkipEqImm { x, nn } => format!("SE V{}, 0x{:02X}", x, nn),
+        Instruction::SkipNeImm { x, nn } => format!("SNE V{}, 0x{:02X}", x, nn),
+        Instruction::SkipEqReg { x, y } => format!("SE V{}, V{}", x, y),
+        Instruction::LoadImm { x, nn } => format!("LD V{}, 0x{:02X}", x, nn),
+        Instruction::AddImm { x, nn } => format!("ADD V{}, 0x{:02X}", x, nn),
+        Instruction::Move { x, y } => format!("LD V{}, V{}", x, y),
+        Instruction::Or { x, y } => format!("OR V{}, V{}", x, y),
+        Instruction::And { x, y } => format!("AND V{}, V{}", x, y),
+        Instruction::Xor { x, y } => format!("XOR V{}, V{}", x, y),
+        Instruction::Add { x, y } => format!("ADD V{}, V{}", x, y),
+        Instruction::Sub { x, y } => format!("SUB V{}, V{}", x, y),
+        Instruction::ShiftRight { x, y } => format!("SHR V{}, V{}", x, y),
+        Instruction::SubN { x, y } => format!("SUBN V{}, V{}", x, y),
+        Instruction::ShiftLeft { x, y } => format!("SHL V{}, V{}", x, y),
+        Instruction::SkipNeReg { x, y } => format!("SNE V{}, V{}", x, y),
+        Instruction::LoadI { nnn } => format!("LD I, 0x{:03X}", nnn),
+        // The address is the next word, which disassembles on its own
+        Instruction::LoadLongI => String::from("LD I, LONG"),
+        Instruction::JumpOffset { nnn, .. } => format!("JP V0, 0x{:03X}", nnn),
+        Instruction::Random { x, nn } => format!("RND V{}, 0x{:02X}", x, nn),
+        Instruction::Draw { x, y, n } => format!("DRW V{}, V{}, {}", x, y, n),
+        Instruction::SkipKey { x } => format!("SKP V{}", x),
+        Instruction::SkipNotKey { x } => format!("SKNP V{}", x),
+        Instruction::LoadDelay { x } => format!("LD V{}, DT", x),
+        Instruction::WaitKey { x } => format!("LD V{}, K", x),
+        Instruction::SetDelay { x } => format!("LD DT, V{}", x),
+        Instruction::SetSound { x } => format!("LD ST, V{}", x),
+        Instruction::AddI { x } => format!("ADD I, V{}", x),
+        Instruction::LoadFont { x } => format!("LD F, V{}", x),
+        Instruction::LoadBigFont { x } => format!("LD HF, V{}", x),
+        Instruction::Bcd { x } => format!("LD B, V{}", x),
+        Instruction::Store { x } => format!("LD [I], V{}", x),
+        Instruction::Load { x } => format!("LD V{}, [I]", x),
+        Instruction::StoreFlags { x } => format!("LD R, V{}", x),
+        Instruction::LoadFlags { x } => format!("LD V{}, R", x),
+        Instruction::AudioPattern => String::from("AUDIO"),
+        Instruction::SelectPlanes { n } => format!("PLANE {}", n),
+        Instruction::SetPitch { x } => format!("PITCH V{}", x),
+        Instruction::Unknown(op) => format!("DW 0x{:04X}", op),
+    }
+}
@@ -0,0 +1,126 @@
+// A decoded opcode. Register operands are indices into V0-VF; `nn` and
+// `nnn` are the immediate byte and address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Nop,
+    Cls,
+    Ret,
+    ScrollDown { n: u8 },
+    ScrollUp { n: u8 },
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    LowRes,
+    HighRes,
+    Jump { nnn: u16 },
+    Call { nnn: u16 },
+    SkipEqImm { x: u8, nn: u8 },
+    SkipNeImm { x: u8, nn: u8 },
+    SkipEqReg { x: u8, y: u8 },
+    LoadImm { x: u8, nn: u8 },
+    AddImm { x: u8, nn: u8 },
+    Move { x: u8, y: u8 },
+    Or { x: u8, y: u8 },
+    And { x: u8, y: u8 },
+    Xor { x: u8, y: u8 },
+    Add { x: u8, y: u8 },
+    Sub { x: u8, y: u8 },
+    ShiftRight { x: u8, y: u8 },
+    SubN { x: u8, y: u8 },
+    ShiftLeft { x: u8, y: u8 },
+    SkipNeReg { x: u8, y: u8 },
+    LoadI { nnn: u16 },
+    // F000 NNNN; the address is in the following word, not the opcode
+    LoadLongI,
+    // X is only used with the BXNN quirk
+    JumpOffset { x: u8, nnn: u16 },
+    Random { x: u8, nn: u8 },
+    Draw { x: u8, y: u8, n: u8 },
+    SkipKey { x: u8 },
+    SkipNotKey { x: u8 },
+    LoadDelay { x: u8 },
+    WaitKey { x: u8 },
+    SetDelay { x: u8 },
+    SetSound { x: u8 },
+    AddI { x: u8 },
+    LoadFont { x: u8 },
+    LoadBigFont { x: u8 },
+    AudioPattern,
+    SelectPlanes { n: u8 },
+    SetPitch { x: u8 },
+    Bcd { x: u8 },
+    Store { x: u8 },
+    Load { x: u8 },
+    StoreFlags { x: u8 },
+    LoadFlags { x: u8 },
+    // Anything the emulator doesn't know how to run
+    Unknown(u16),
+}
+
+// Splits an opcode into its nibbles and works out which instruction it is,
+// without touching any machine state
+pub fn decode(op: u16) -> Instruction {
+    let hex_1 = (op & 0xF000) >> 12;
+    let hex_2 = (op & 0x0F00) >> 8;
+    let hex_3 = (op & 0x00F0) >> 4;
+    let hex_4 = op & 0x000F;
+
+    let x = hex_2 as u8;
+    let y = hex_3 as u8;
+    let n = hex_4 as u8;
+    let nn = (op & 0xFF) as u8;
+    let nnn = op & 0xFFF;
+
+    match (hex_1, hex_2, hex_3, hex_4) {
+        (0, 0, 0, 0) => Instruction::Nop,
+        (0, 0, 0xE, 0) => Instruction::Cls,
+        (0, 0, 0xE, 0xE) => Instruction::Ret,
+        (0, 0, 0xC, _) => Instruction::ScrollDown { n },
+        (0, 0, 0xD, _) => Instruction::ScrollUp { n },
+        (0, 0, 0xF, 0xB) => Instruction::ScrollRight,
+        (0, 0, 0xF, 0xC) => Instruction::ScrollLeft,
+        (0, 0, 0xF, 0xD) => Instruction::Exit,
+        (0, 0, 0xF, 0xE) => Instruction::LowRes,
+        (0, 0, 0xF, 0xF) => Instruction::HighRes,
+        (1, _, _, _) => Instruction::Jump { nnn },
+        (2, _, _, _) => Instruction::Call { nnn },
+        (3, _, _, _) => Instruction::SkipEqImm { x, nn },
+        (4, _, _, _) => Instruction::SkipNeImm { x, nn },
+        (5, _, _, 0) => Instruction::SkipEqReg { x, y },
+        (6, _, _, _) => Instruction::LoadImm { x, nn },
+        (7, _, _, _) => Instruction::AddImm { x, nn },
+        (8, _, _, 0) => Instruction::Move { x, y },
+        (8, _, _, 1) => Instruction::Or { x, y },
+        (8, _, _, 2) => Instruction::And { x, y },
+        (8, _, _, 3) => Instruction::Xor { x, y },
+        (8, _, _, 4) => Instruction::Add { x, y },
+        (8, _, _, 5) => Instruction::Sub { x, y },
+        (8, _, _, 6) => Instruction::ShiftRight { x, y },
+        (8, _, _, 7) => Instruction::SubN { x, y },
+        (8, _, _, 0xE) => Instruction::ShiftLeft { x, y },
+        (9, _, _, 0) => Instruction::SkipNeReg { x, y },
+        (0xA, _, _, _) => Instruction::LoadI { nnn },
+        (0xB, _, _, _) => Instruction::JumpOffset { x, nnn },
+        (0xC, _, _, _) => Instruction::Random { x, nn },
+        (0xD, _, _, _) => Instruction::Draw { x, y, n },
+        (0xE, _, 9, 0xE) => Instruction::SkipKey { x },
+        (0xE, _, 0xA, 1) => Instruction::SkipNotKey { x },
+        (0xF, 0, 0, 0) => Instruction::LoadLongI,
+        (0xF, 0, 0, 2) => Instruction::AudioPattern,
+        (0xF, _, 0, 1) => Instruction::SelectPlanes { n: x },
+        (0xF, _, 0, 7) => Instruction::LoadDelay { x },
+        (0xF, _, 0, 0xA) => Instruction::WaitKey { x },
+        (0xF, _, 1, 5) => Instruction::SetDelay { x },
+        (0xF, _, 1, 8) => Instruction::SetSound { x },
+        (0xF, _, 1, 0xE) => Instruction::AddI { x },
+        (0xF, _, 2, 9) => Instruction::LoadFont { x },
+        (0xF, _, 3, 0) => Instruction::LoadBigFont { x },
+        (0xF, _, 3, 3) => Instruction::Bcd { x },
+        (0xF, _, 3, 0xA) => Instruction::SetPitch { x },
+        (0xF, _, 5, 5) => Instruction::Store { x },
+        (0xF, _, 6, 5) => Instruction::Load { x },
+        (0xF, _, 7, 5) => Instruction::StoreFlags { x },
+        (0xF, _, 8, 5) => Instruction::LoadFlags { x },
+        (_, _, _, _) => Instruction::Unknown(op),
+    }
+}
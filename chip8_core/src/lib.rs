@@ -1,16 +1,103 @@
-use rand::random;
+// The core runs without std when the default `std` feature is turned off.
+// It still needs `alloc` either way: RAM is a Vec, sized when the emulator
+// is built, the rewind history and the host hooks live on the heap, and
+// everything that returns a Vec or String allocates, get_display included.
+// With std off, Emulator::new() can't seed its RNG from the OS, so it uses
+// a fixed seed; use Emulator::with_rng to vary it.
+#![cfg_attr(not(feature = "std"), no_std)]
+#![allow(clippy::needless_return)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Index;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::Path;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "serde")]
+mod serde_arrays;
+
+mod asm;
+mod builder;
+mod disasm;
+mod instruction;
+mod rng;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use asm::{assemble, AsmError};
+pub use builder::EmulatorBuilder;
+pub use disasm::disassemble;
+pub use instruction::{decode, Instruction};
+pub use rng::LfsrRng;
+use rng::Random;
+#[cfg(feature = "wasm")]
+pub use wasm::WasmEmulator;
 
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
 
+// SUPER-CHIP high resolution mode
+pub const HIRES_SCREEN_WIDTH: usize = 128;
+pub const HIRES_SCREEN_HEIGHT: usize = 64;
+
+// The screen buffer is sized for hi-res; low res only uses the front of it
+const SCREEN_BUF_SIZE: usize = HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT;
+
+// Each plane is a bitset, a u64 per 64 pixels. Pixel n of the row-major
+// order get_display uses is bit n % 64 of word n / 64, so a row is one word
+// in low res and two in hi-res.
+const PLANE_WORDS: usize = SCREEN_BUF_SIZE / 64;
+type Plane = [u64; PLANE_WORDS];
+
+// XO-CHIP draws on two bit planes
+const NUM_PLANES: usize = 2;
+
+// Default RAM; with_ram_size goes up to the 64KB a 16-bit I can address
 const RAM_SIZE: usize = 4096;
+const MAX_RAM_SIZE: usize = 0x10000;
 const NUM_REGS: usize = 16;
 const STACK_SIZE: usize = 16;
 const NUM_KEYS: usize = 16;
+// SUPER-CHIP's RPL user flags, saved and restored by FX75/FX85
+const NUM_FLAGS: usize = 8;
 
 const START_ADDR: u16 = 0x200;
+// Programs can't start inside the fonts
+const MIN_START_ADDR: u16 = (BIG_FONT_ADDR + BIG_FONTSET_SIZE) as u16;
+
+// Timers and the display run at 60Hz; the CPU clock is set per emulator
+const TIMER_HZ: u32 = 60;
+const DEFAULT_CLOCK_HZ: u32 = 700;
+
+// Each rewind frame keeps a full copy of RAM and the screen (~6KB with
+// the default RAM), so cap the history at ten seconds
+const MAX_REWIND_FRAMES: usize = 600;
+
+const DEFAULT_TONE_HZ: f32 = 440.0;
+const BEEP_AMPLITUDE: f32 = 0.25;
+
+// XO-CHIP audio: a 128 bit pattern played at 4000 bits/s at the default pitch
+const AUDIO_PATTERN_SIZE: usize = 16;
+const DEFAULT_PITCH: u8 = 64;
 
-const FONTSET_SIZE: usize = 80;
+// 16 glyphs, 5 bytes each; set_fontset takes at most this many bytes
+pub const FONTSET_SIZE: usize = 80;
 const FONTSET: [u8; FONTSET_SIZE] = [ 
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0 
     0x20, 0x60, 0x20, 0x20, 0x70, // 1 
@@ -30,211 +117,1712 @@ const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80 // F
 ];
 
+// SUPER-CHIP 8x10 digits, 10 bytes per glyph, used by FX30.
+// Low RAM layout: small font at 0x000-0x04F, big font at 0x050-0x0EF,
+// leaving everything from 0x0F0 up to START_ADDR unused.
+const BIG_FONT_ADDR: usize = FONTSET_SIZE;
+const BIG_FONTSET_SIZE: usize = 160;
+const BIG_FONTSET: [u8; BIG_FONTSET_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0  // F
+];
+
+// Behaviour of the opcodes that differ between CHIP-8 interpreters.
+// The defaults follow the original COSMAC VIP interpreter, except for the
+// shifts and lo-res DXY0, which keep the behaviour most ROMs in circulation
+// expect, and display_wait, which is opt-in since it changes how many
+// instructions a frame runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct QuirkConfig {
+    // 8XY6/8XYE copy VY into VX before shifting
+    pub shift_uses_vy: bool,
+    // BNNN jumps to XNN + VX instead of NNN + V0
+    pub jump_with_offset_vx: bool,
+    // FX55/FX65 leave I pointing past the last register touched
+    pub load_store_increments_i: bool,
+    // 8XY1/8XY2/8XY3 set VF to 0
+    pub logic_resets_vf: bool,
+    // Sprites wrap around the screen edges instead of being clipped
+    pub sprite_wrapping: bool,
+    // DXYN waits for the next vertical blank, so run_frame ends a frame
+    // early after a DRAW and at most one DRAW runs per frame
+    pub display_wait: bool,
+    // DXY0 draws a 16x16 sprite in lo-res as well as hi-res, like XO-CHIP.
+    // Off, it draws nothing in lo-res, as on the VIP.
+    pub lores_big_sprites: bool,
+    // 0NNN calls to VIP machine code routines, which can't be emulated,
+    // are skipped over like NOPs. Off, they fault with UnknownOpcode.
+    pub machine_routine_nop: bool,
+    // In hi-res, DXYN sets VF to the number of sprite rows that collided or
+    // were clipped off the bottom, as SUPER-CHIP 1.1 does, rather than 0/1
+    pub schip_collision_count: bool,
+    // EX9E/EXA1 only see a key as down during the frame it was pressed,
+    // see Emulator::key_just_pressed, so a held key only counts at first
+    pub edge_triggered_keys: bool,
+}
+
+impl Default for QuirkConfig {
+    fn default() -> Self {
+        return Self {
+            shift_uses_vy: false,
+            jump_with_offset_vx: false,
+            load_store_increments_i: true,
+            logic_resets_vf: true,
+            sprite_wrapping: false,
+            display_wait: false,
+            lores_big_sprites: true,
+            machine_routine_nop: false,
+            schip_collision_count: false,
+            edge_triggered_keys: false,
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadError {
+    // The ROM doesn't fit between the start address and the end of RAM
+    TooLarge { size: usize, max: usize },
+    // A RAM image for load_ram that isn't exactly the size of RAM
+    WrongSize { size: usize, expected: usize },
+    // A ROM with no bytes in it, usually a frontend reading the wrong file
+    Empty,
+    // Two load_segments blocks, by start address, share some bytes
+    Overlap { first: u16, second: u16 },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::TooLarge { size, max } => {
+                write!(f, "ROM is {} bytes, but at most {} bytes fit in RAM", size, max)
+            },
+            LoadError::WrongSize { size, expected } => {
+                write!(f, "RAM image is {} bytes, but RAM is {} bytes", size, expected)
+            },
+            LoadError::Empty => write!(f, "ROM is empty"),
+            LoadError::Overlap { first, second } => {
+                write!(f, "segments at {:#05X} and {:#05X} overlap", first, second)
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LoadError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecError {
+    // CALL with all STACK_SIZE return slots in use
+    StackOverflow,
+    // RET with nothing on the stack
+    StackUnderflow,
+    // The program ran 00FD; nothing more runs until reset
+    Halted,
+    // PC reached a breakpoint; the instruction there hasn't run yet
+    Breakpoint(u16),
+    // An opcode this emulator doesn't implement
+    UnknownOpcode(u16),
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecError::StackOverflow => write!(f, "stack overflow: more than {} nested calls", STACK_SIZE),
+            ExecError::StackUnderflow => write!(f, "stack underflow: return with an empty stack"),
+            ExecError::Halted => write!(f, "the program has exited"),
+            ExecError::Breakpoint(addr) => write!(f, "breakpoint at {:#05X}", addr),
+            ExecError::UnknownOpcode(op) => write!(f, "unimplemented opcode: {:#06X}", op),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ExecError {}
+
+// Every fault try_tick can report. ExecError already covers them all:
+// memory accesses wrap around RAM, so there's no out-of-bounds case.
+pub type Fault = ExecError;
+
+// Something in the machine state that would make the next tick misbehave,
+// e.g. from a corrupt or hand-edited save
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateError {
+    // RAM smaller than the default or larger than 64KB
+    RamSize(usize),
+    // PC points past the end of RAM
+    PcOutOfRange(u16),
+    // More return addresses than the stack holds
+    SpOutOfRange(u16),
+    // A return address on the stack points past the end of RAM
+    ReturnOutOfRange(u16),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::RamSize(size) => write!(f, "invalid RAM size: {} bytes", size),
+            StateError::PcOutOfRange(pc) => write!(f, "PC out of range: {:#06X}", pc),
+            StateError::SpOutOfRange(sp) => write!(f, "SP out of range: {}, the stack holds {}", sp, STACK_SIZE),
+            StateError::ReturnOutOfRange(addr) => write!(f, "return address out of range: {:#06X}", addr),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StateError {}
+
+// Which bit of each packed display byte holds the leftmost of its 8 pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitOrder {
+    // Pixel 0 in bit 7 (0x80), pixel 7 in bit 0
+    MsbFirst,
+    // Pixel 0 in bit 0 (0x01), pixel 7 in bit 7, as many LED drivers expect
+    LsbFirst,
+}
+
+// One row of the display, as yielded by Emulator::rows. It reads the
+// pixels straight out of the screen rather than copying them, and indexes
+// like a &[bool].
+#[derive(Clone, Copy)]
+pub struct Row<'a> {
+    plane: &'a Plane,
+    start: usize,
+    width: usize,
+}
+
+impl<'a> Row<'a> {
+    pub fn len(&self) -> usize {
+        return self.width;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.width == 0;
+    }
+
+    // Whether pixel `x` of the row is lit, or None past its end
+    pub fn get(&self, x: usize) -> Option<bool> {
+        if x >= self.width {
+            return None;
+        }
+        return Some(plane_bit(self.plane, self.start + x));
+    }
+
+    // The row's pixels, left to right
+    pub fn iter(&self) -> impl Iterator<Item = bool> + 'a {
+        let plane = self.plane;
+        return (self.start..self.start + self.width).map(move |idx| plane_bit(plane, idx));
+    }
+}
+
+impl Index<usize> for Row<'_> {
+    type Output = bool;
+
+    fn index(&self, x: usize) -> &bool {
+        match self.get(x) {
+            Some(true) => return &true,
+            Some(false) => return &false,
+            None => panic!("pixel {} is past the end of a {} pixel row", x, self.width),
+        }
+    }
+}
+
+impl fmt::Debug for Row<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return f.debug_list().entries(self.iter()).finish();
+    }
+}
+
+// The 16 keys of the hex keypad
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Key0, Key1, Key2, Key3,
+    Key4, Key5, Key6, Key7,
+    Key8, Key9, KeyA, KeyB,
+    KeyC, KeyD, KeyE, KeyF,
+}
+
+impl From<Key> for usize {
+    fn from(key: Key) -> usize {
+        return key as usize;
+    }
+}
+
+// An instruction run by Emulator::step, and the address it was fetched from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExecutedInstruction {
+    pub pc: u16,
+    pub opcode: u16,
+    // The address word that follows F000, the one 4-byte instruction
+    pub operand: Option<u16>,
+}
+
+// A copy of the registers and display, for inspecting or golden-state tests.
+// RAM and XO-CHIP's second plane aren't included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EmulatorState {
+    pub pc: u16,
+    pub i: u16,
+    pub sp: u16,
+    pub v_reg: [u8; NUM_REGS],
+    pub stack: [u16; STACK_SIZE],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub hi_res: bool,
+    // As returned by get_display
+    pub screen: Vec<bool>,
+}
+
+// Machine state captured by run_frame for rewinding, down to the counters
+// and audio phase. The keys held now aren't included, so rewinding doesn't
+// undo whatever the player is holding down, and nor are settings such as
+// the clock speed, quirks and breakpoints.
+#[derive(Clone)]
+struct RewindPoint {
+    pc: u16,
+    ram: Vec<u8>,
+    screen: Plane,
+    screen_2: Plane,
+    plane_mask: u8,
+    hi_res: bool,
+    last_draw_collisions: u32,
+    last_draw_bounds: Option<(usize, usize, usize, usize)>,
+    vblank_wait: bool,
+    v_reg: [u8; NUM_REGS],
+    i_reg: u16,
+    sp: u16,
+    stack: [u16; STACK_SIZE],
+    flags: [u8; NUM_FLAGS],
+    frame_keys: [bool; NUM_KEYS],
+    prev_keys: [bool; NUM_KEYS],
+    wait_key: Option<usize>,
+    delay_t: u8,
+    sound_t: u8,
+    halted: bool,
+    idle: bool,
+    cycles: u64,
+    frames: u64,
+    opcode_counts: [u64; 16],
+    cycle_remainder: u32,
+    timer_remainder: u32,
+    audio_phase: f32,
+    audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pattern_loaded: bool,
+    pitch: u8,
+    rng: Random,
+}
+
+// Callbacks installed by the host. Closures can't be cloned, so a clone of an
+// Emulator starts out with none. They're Send so the Emulator stays Send,
+// e.g. for running it on its own thread.
+#[derive(Default)]
+struct Hooks {
+    // Called before each instruction runs
+    trace: Option<Box<dyn FnMut(ExecutedInstruction) + Send>>,
+    // Called with the address when the program overwrites code it has run
+    smc: Option<Box<dyn FnMut(u16) + Send>>,
+    // One entry per RAM byte fetched as an opcode while smc is set,
+    // empty otherwise
+    executed: Vec<bool>,
+}
+
+impl Clone for Hooks {
+    fn clone(&self) -> Self {
+        return Self::default();
+    }
+}
+
+// With the serde feature the whole machine can be saved and restored.
+// Clones are independent, down to their own copy of the RNG's state.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Emulator {
     pc: u16,
-    ram: [u8; RAM_SIZE],
-    screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    ram: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "serde_arrays"))]
+    screen: Plane,
+    // XO-CHIP's second plane; `screen` is the first
+    #[cfg_attr(feature = "serde", serde(with = "serde_arrays"))]
+    screen_2: Plane,
+    // Bit n set when plane n is drawn to by DRAW, CLS and the scrolls
+    plane_mask: u8,
+    hi_res: bool,
+    // Bit n set when row n of the screen changed since take_dirty_rows
+    dirty_rows: u64,
+    // Set by anything that touches the display, cleared by take_draw_flag
+    draw_flag: bool,
+    // Pixels the most recent DRAW turned off
+    last_draw_collisions: u32,
+    // (min_x, min_y, max_x, max_y) of the pixels the most recent DRAW flipped
+    last_draw_bounds: Option<(usize, usize, usize, usize)>,
+    // A DRAW is waiting for vblank, see QuirkConfig::display_wait
+    vblank_wait: bool,
     v_reg: [u8; NUM_REGS],
     i_reg: u16,
     sp: u16,
     stack: [u16; STACK_SIZE],
+    flags: [u8; NUM_FLAGS],
     keys: [bool; NUM_KEYS],
+    // Key states at the start of the current frame and the one before. A key
+    // down at both has been held too long for key_just_pressed.
+    frame_keys: [bool; NUM_KEYS],
+    prev_keys: [bool; NUM_KEYS],
+    // Key latched by FX0A, waiting to be released
+    wait_key: Option<usize>,
     delay_t: u8,
     sound_t: u8,
+    halted: bool,
+    // The last instruction was a jump to itself
+    idle: bool,
+    // Instructions executed since the last reset
+    cycles: u64,
+    // run_frame calls since the last reset
+    frames: u64,
+    // Instructions executed per opcode family (high nibble)
+    opcode_counts: [u64; 16],
+    clock_hz: u32,
+    // Cycles owed to the next frame when clock_hz doesn't divide by 60
+    cycle_remainder: u32,
+    // Elapsed time towards the next timer tick for tick_timers_dt, in
+    // sixtieths of a millisecond
+    timer_remainder: u32,
+    tone_hz: f32,
+    // Position within the current square wave period or audio pattern, 0.0 to 1.0
+    audio_phase: f32,
+    audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    // Until a program loads a pattern with F002 the plain square wave plays
+    pattern_loaded: bool,
+    pitch: u8,
+    quirks: QuirkConfig,
+    // Where programs are loaded and run from, 0x200 unless changed
+    start_addr: u16,
+    // Size of the program last loaded with load, 0 since a reset
+    rom_len: usize,
+    // Copied into low RAM by new and reset
+    #[cfg_attr(feature = "serde", serde(with = "serde_arrays"))]
+    fontset: [u8; FONTSET_SIZE],
+    // Set by the frontend's pause button, so not part of a save
+    #[cfg_attr(feature = "serde", serde(skip))]
+    paused: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    breakpoints: BTreeSet<u16>,
+    // Set once a breakpoint has been reported, so the next step runs past it
+    #[cfg_attr(feature = "serde", serde(skip))]
+    resume_from: Option<u16>,
+    // What's left of a frame run_frames stopped partway through, so the
+    // next run_frame or run_frames finishes it instead of starting afresh
+    #[cfg_attr(feature = "serde", serde(skip))]
+    frame_ticks_left: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    hooks: Hooks,
+    // Oldest first, at most rewind_frames long
+    #[cfg_attr(feature = "serde", serde(skip))]
+    history: VecDeque<RewindPoint>,
+    rewind_frames: usize,
+    // Save states don't carry the RNG; a restored emulator gets a fresh one
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_rng"))]
+    rng: Random,
 }
 
 
 
 
 
+#[cfg(feature = "std")]
+fn default_rng() -> Random {
+    return Random::Std(StdRng::from_entropy());
+}
+
+#[cfg(not(feature = "std"))]
+fn default_rng() -> Random {
+    return Random::Std(StdRng::seed_from_u64(0));
+}
+
+// Pixel `idx` of a plane, in get_display's order
+fn plane_bit(plane: &Plane, idx: usize) -> bool {
+    return plane[idx / 64] & (1 << (idx % 64)) != 0;
+}
+
 impl Emulator {
     pub fn new() -> Self {
         let mut new_emulator = Self {
             pc: START_ADDR,
-            ram: [0; RAM_SIZE],
-            screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            ram: vec![0; RAM_SIZE],
+            screen: [0; PLANE_WORDS],
+            screen_2: [0; PLANE_WORDS],
+            plane_mask: 1,
+            hi_res: false,
+            dirty_rows: 0,
+            draw_flag: false,
+            last_draw_collisions: 0,
+            last_draw_bounds: None,
+            vblank_wait: false,
             v_reg: [0; NUM_REGS],
             i_reg: 0,
             sp: 0,
             stack: [0; STACK_SIZE],
+            flags: [0; NUM_FLAGS],
             keys: [false; NUM_KEYS],
+            frame_keys: [false; NUM_KEYS],
+            prev_keys: [false; NUM_KEYS],
+            wait_key: None,
             delay_t: 0,
             sound_t: 0,
+            halted: false,
+            idle: false,
+            cycles: 0,
+            frames: 0,
+            opcode_counts: [0; 16],
+            clock_hz: DEFAULT_CLOCK_HZ,
+            cycle_remainder: 0,
+            timer_remainder: 0,
+            tone_hz: DEFAULT_TONE_HZ,
+            audio_phase: 0.0,
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pattern_loaded: false,
+            pitch: DEFAULT_PITCH,
+            quirks: QuirkConfig::default(),
+            start_addr: START_ADDR,
+            rom_len: 0,
+            fontset: FONTSET,
+            paused: false,
+            breakpoints: BTreeSet::new(),
+            resume_from: None,
+            frame_ticks_left: None,
+            hooks: Hooks::default(),
+            history: VecDeque::new(),
+            rewind_frames: 0,
+            rng: default_rng(),
         };
 
         new_emulator.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        new_emulator.ram[BIG_FONT_ADDR..BIG_FONT_ADDR + BIG_FONTSET_SIZE].copy_from_slice(&BIG_FONTSET);
+
+        return new_emulator;
+    }
+
+    // Seeds CXNN's random numbers so runs can be reproduced exactly
+    pub fn with_rng(seed: u64) -> Self {
+        let mut new_emulator = Self::new();
+        new_emulator.set_rng(Random::Std(StdRng::seed_from_u64(seed)));
+        return new_emulator;
+    }
+
+    // CXNN draws from an LfsrRng instead, for replays that must come out the
+    // same on every host
+    pub fn with_lfsr_rng(seed: u16) -> Self {
+        let mut new_emulator = Self::new();
+        new_emulator.set_rng(Random::Lfsr(LfsrRng::new(seed)));
+        return new_emulator;
+    }
+
+    // For variants like the ETI-660 that load programs at 0x600. Panics if
+    // the address is inside the fonts or past the end of RAM.
+    pub fn with_start_addr(addr: u16) -> Self {
+        let mut new_emulator = Self::new();
+        new_emulator.set_start_addr(addr);
+        return new_emulator;
+    }
+
+    // For variants with more than 4KB of RAM, e.g. XO-CHIP's 64KB. Panics
+    // if smaller than the default or larger than 64KB.
+    pub fn with_ram_size(bytes: usize) -> Self {
+        let mut new_emulator = Self::new();
+        new_emulator.resize_ram(bytes);
+        return new_emulator;
+    }
+
+    // The checks behind with_ram_size and with_start_addr, shared with
+    // EmulatorBuilder so the two ways of configuring these can't disagree
+    pub(crate) fn resize_ram(&mut self, bytes: usize) {
+        assert!((RAM_SIZE..=MAX_RAM_SIZE).contains(&bytes), "invalid RAM size: {}", bytes);
+        self.ram.resize(bytes, 0);
+    }
+
+    pub(crate) fn set_start_addr(&mut self, addr: u16) {
+        assert!(
+            addr >= MIN_START_ADDR && (addr as usize) < self.ram.len(),
+            "invalid start address: {:#05X}", addr
+        );
+        self.start_addr = addr;
+        self.pc = addr;
+    }
+
+    pub(crate) fn set_rng(&mut self, rng: Random) {
+        self.rng = rng;
+    }
 
+    pub fn with_quirks(quirks: QuirkConfig) -> Self {
+        let mut new_emulator = Self::new();
+        new_emulator.quirks = quirks;
         return new_emulator;
     }
 
-    pub fn get_display(&self) -> &[bool] {
-        return &self.screen;
+    pub fn get_quirks(&self) -> QuirkConfig {
+        return self.quirks;
+    }
+
+    pub fn set_quirks(&mut self, quirks: QuirkConfig) {
+        self.quirks = quirks;
+    }
+
+    // Row-major, display_dimensions() wide. This is the first plane, which
+    // is the whole picture for anything but XO-CHIP. The screen is stored as
+    // bits, so this builds a new Vec each call; per-frame drawing should use
+    // pixel, rows, lit_pixels or pack_display_into instead.
+    pub fn get_display(&self) -> Vec<bool> {
+        return self.get_plane(0);
+    }
+
+    // One XO-CHIP plane, laid out like get_display. Panics for planes past 1.
+    pub fn get_plane(&self, plane: usize) -> Vec<bool> {
+        assert!(plane < NUM_PLANES, "no such plane: {}", plane);
+        let (width, height) = self.display_dimensions();
+        let bits = self.plane(plane);
+        return (0..width * height).map(|idx| plane_bit(bits, idx)).collect();
+    }
+
+    // Whether the pixel at (x, y) in the first plane is lit, without
+    // building the whole of get_display. False off the edge of the screen.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        let (width, height) = self.display_dimensions();
+        return x < width && y < height && plane_bit(&self.screen, x + width * y);
+    }
+
+    // Both planes combined at one byte per pixel: bit 0 from the first
+    // plane, bit 1 from the second, giving a colour index of 0-3
+    pub fn get_display_planes(&self) -> Vec<u8> {
+        return self.get_plane(0).iter()
+            .zip(self.get_plane(1).iter())
+            .map(|(p1, p2)| (*p1 as u8) | ((*p2 as u8) << 1))
+            .collect();
+    }
+
+    // The display at one bit per pixel, row-major, MSB-first within each byte.
+    // That's SCREEN_WIDTH * SCREEN_HEIGHT / 8 = 256 bytes in low res,
+    // and 1024 bytes in hi-res.
+    pub fn get_display_packed(&self) -> Vec<u8> {
+        let (width, height) = self.display_dimensions();
+        let mut buf = vec![0; width * height / 8];
+        self.pack_display_into(&mut buf, BitOrder::MsbFirst);
+        return buf;
+    }
+
+    // As get_display_packed, writing into the front of `buf` with each byte's
+    // bits in the given order. Panics if `buf` is too short.
+    pub fn pack_display_into(&self, buf: &mut [u8], order: BitOrder) {
+        let (width, height) = self.display_dimensions();
+        let len = width * height / 8;
+        assert!(buf.len() >= len, "buffer too small for packed display");
+
+        // The planes already store pixels LSB-first, 8 to a byte
+        for (idx, byte) in buf[..len].iter_mut().enumerate() {
+            let bits = (self.screen[idx / 8] >> (8 * (idx % 8))) as u8;
+            *byte = match order {
+                BitOrder::MsbFirst => bits.reverse_bits(),
+                BitOrder::LsbFirst => bits,
+            };
+        }
     }
 
+    // A 64-bit FNV-1a hash of the display's pixels, for comparing frames
+    // without keeping them around. Stable across runs and hosts, and a
+    // blank low res screen hashes differently from a blank hi-res one.
+    pub fn display_hash(&self) -> u64 {
+        let (width, height) = self.display_dimensions();
+        let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+        for word in &self.screen[..width * height / 64] {
+            for byte in word.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+            }
+        }
+        return hash;
+    }
+
+    // The display a row at a time, top to bottom, each as wide as the
+    // active resolution. Nothing is allocated: each Row borrows the screen.
+    pub fn rows(&self) -> impl Iterator<Item = Row<'_>> + '_ {
+        let (width, height) = self.display_dimensions();
+        let plane = &self.screen;
+        return (0..height).map(move |y| Row { plane, start: y * width, width });
+    }
+
+    // (x, y) of each lit pixel, row by row from the top left
+    pub fn lit_pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let (width, height) = self.display_dimensions();
+        return (0..width * height)
+            .filter(|&idx| plane_bit(&self.screen, idx))
+            .map(move |idx| (idx % width, idx / width));
+    }
+
+    // Expands the display into an RGBA image so frontends can upload it
+    // straight to a texture. `scale` is the block size of a hi-res pixel;
+    // lo-res pixels are doubled on top of that, so both modes fill the same
+    // (128 * scale) x (64 * scale) image. `out` is resized to fit, 4 bytes
+    // per pixel.
+    pub fn render_rgba(&self, scale: usize, fg: [u8; 4], bg: [u8; 4], out: &mut Vec<u8>) {
+        let (width, _) = self.display_dimensions();
+        let block = scale * HIRES_SCREEN_WIDTH / width;
+
+        out.clear();
+        out.reserve(HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT * scale * scale * 4);
+        for row in self.rows() {
+            for _ in 0..block {
+                for pixel in row.iter() {
+                    let color = if pixel { fg } else { bg };
+                    for _ in 0..block {
+                        out.extend_from_slice(&color);
+                    }
+                }
+            }
+        }
+    }
+
+    // The display as text, '█' for lit pixels and ' ' for dark ones, a line
+    // per row. Handy for dumping what's on screen into a log.
+    pub fn display_to_string(&self) -> String {
+        let (width, height) = self.display_dimensions();
+        let mut out = String::with_capacity((width * '█'.len_utf8() + 1) * height);
+        for row in self.rows() {
+            for pixel in row.iter() {
+                out.push(if pixel { '█' } else { ' ' });
+            }
+            out.push('\n');
+        }
+        return out;
+    }
+
+    // Draws an 8-pixel-wide sprite from a host buffer, one byte per row,
+    // without going through RAM or I. Clipping, wrapping and the selected
+    // planes work as for DXYN; returns what DXYN would put in VF, but
+    // leaves VF alone.
+    pub fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        if sprite.is_empty() {
+            return false;
+        }
+        return self.draw_rows(x, y, sprite.len(), 8, |_, row| (sprite[row % sprite.len()] as u16) << 8).0;
+    }
+
+    // Blanks the whole display, including both XO-CHIP planes
+    pub fn clear_screen(&mut self) {
+        self.clear_planes(0b11);
+    }
+
+    // Rows changed since the last call, bit n for row n, so frontends can
+    // redraw only those scanlines
+    pub fn take_dirty_rows(&mut self) -> u64 {
+        let rows = self.dirty_rows;
+        self.dirty_rows = 0;
+        return rows;
+    }
+
+    // Whether a DRAW, CLS or scroll ran since the last call, so frontends
+    // can skip re-rendering frames where nothing happened
+    pub fn take_draw_flag(&mut self) -> bool {
+        let drawn = self.draw_flag;
+        self.draw_flag = false;
+        return drawn;
+    }
+
+    // How many pixels the last DRAW erased. VF only says whether it was any.
+    pub fn last_collision_count(&self) -> u32 {
+        return self.last_draw_collisions;
+    }
+
+    // The rectangle the last DRAW changed, as (min_x, min_y, max_x, max_y)
+    // inclusive, so frontends can redraw just that. None if it changed nothing.
+    pub fn last_draw_bounds(&self) -> Option<(usize, usize, usize, usize)> {
+        return self.last_draw_bounds;
+    }
+
+    // (width, height) of the active resolution
+    pub fn display_dimensions(&self) -> (usize, usize) {
+        if self.hi_res {
+            return (HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT);
+        } else {
+            return (SCREEN_WIDTH, SCREEN_HEIGHT);
+        }
+    }
+
+    // Rows the DXYN `op` would draw right now, 0 for anything else. N=0 is
+    // 16 rows in hi-res, and in lo-res depends on
+    // QuirkConfig::lores_big_sprites.
+    pub fn sprite_height(&self, op: u16) -> usize {
+        match decode(op) {
+            Instruction::Draw { n, .. } => return self.draw_height(n),
+            _ => return 0,
+        }
+    }
+
+    fn draw_height(&self, n: u8) -> usize {
+        if n != 0 {
+            return n as usize;
+        } else if self.hi_res || self.quirks.lores_big_sprites {
+            return 16;
+        } else {
+            return 0;
+        }
+    }
+
+    pub fn cycle_count(&self) -> u64 {
+        return self.cycles;
+    }
+
+    // Frames run since the last reset, for lining up replayed input.
+    // Rewinding winds it back too.
+    pub fn frame_count(&self) -> u64 {
+        return self.frames;
+    }
+
+    // How many instructions from each opcode family (0x0NNN to 0xFNNN) have
+    // run, indexed by the high nibble. Unknown opcodes are counted too.
+    pub fn opcode_histogram(&self) -> [u64; 16] {
+        return self.opcode_counts;
+    }
+
+    pub fn reset_opcode_histogram(&mut self) {
+        self.opcode_counts = [0; 16];
+    }
+
+    // True while the program is stuck jumping to itself, so frontends can throttle
+    pub fn is_idle(&self) -> bool {
+        return self.idle;
+    }
+
+    pub fn is_halted(&self) -> bool {
+        return self.halted;
+    }
+
+    // While paused, tick, run_frame and tick_timers do nothing and the beep
+    // stops, but keys still register. step still runs, for debuggers.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        return self.paused;
+    }
+
+    pub fn is_hi_res(&self) -> bool {
+        return self.hi_res;
+    }
+
+    // Register access for debuggers. V register indices above 0xF panic.
+    pub fn get_v_reg(&self, idx: usize) -> u8 {
+        assert!(idx < NUM_REGS, "V register index out of range: {}", idx);
+        return self.v_reg[idx];
+    }
+
+    pub fn set_v_reg(&mut self, idx: usize, val: u8) {
+        assert!(idx < NUM_REGS, "V register index out of range: {}", idx);
+        self.v_reg[idx] = val;
+    }
+
+    pub fn get_i(&self) -> u16 {
+        return self.i_reg;
+    }
+
+    // Wrapped into RAM the way addresses worked out from I are, so with the
+    // default 4KB that's the low 12 bits
+    pub fn set_i(&mut self, val: u16) {
+        self.i_reg = self.ram_addr(val as usize) as u16;
+    }
+
+    pub fn get_pc(&self) -> u16 {
+        return self.pc;
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn get_sp(&self) -> u16 {
+        return self.sp;
+    }
+
+    // sp counts the occupied stack slots, so it can't exceed STACK_SIZE
+    pub fn set_sp(&mut self, sp: u16) {
+        assert!(sp as usize <= STACK_SIZE, "stack pointer out of range: {}", sp);
+        self.sp = sp;
+    }
+
+    // Return addresses of the calls in progress, outermost first
+    pub fn stack_frames(&self) -> &[u16] {
+        return &self.stack[..self.stack_depth()];
+    }
+
+    // Calls in progress. Capped at the stack size in case a restored state
+    // has a bad sp, see validate.
+    pub fn stack_depth(&self) -> usize {
+        return (self.sp as usize).min(STACK_SIZE);
+    }
+
+    pub fn get_delay_timer(&self) -> u8 {
+        return self.delay_t;
+    }
+
+    pub fn set_delay_timer(&mut self, val: u8) {
+        self.delay_t = val;
+    }
+
+    pub fn get_sound_timer(&self) -> u8 {
+        return self.sound_t;
+    }
+
+    pub fn set_sound_timer(&mut self, val: u8) {
+        self.sound_t = val;
+    }
+
+    // RAM access for debuggers. Addresses past the end of RAM panic,
+    // as does a range that would run off the end of RAM.
+    pub fn read_mem(&self, addr: u16) -> u8 {
+        assert!((addr as usize) < self.ram.len(), "address out of range: {:#06X}", addr);
+        return self.ram[addr as usize];
+    }
+
+    pub fn write_mem(&mut self, addr: u16, val: u8) {
+        assert!((addr as usize) < self.ram.len(), "address out of range: {:#06X}", addr);
+        self.ram[addr as usize] = val;
+    }
+
+    pub fn read_mem_range(&self, start: u16, len: u16) -> &[u8] {
+        let start = start as usize;
+        let end = start + len as usize;
+        assert!(end <= self.ram.len(), "range {:#06X}..{:#06X} out of range", start, end);
+        return &self.ram[start..end];
+    }
+
+    // A copy of all of RAM, for saving alongside a snapshot
+    pub fn dump_ram(&self) -> Vec<u8> {
+        return self.ram.clone();
+    }
+
+    // Replaces all of RAM with an image from dump_ram. It has to be the
+    // same size as RAM; nothing is written otherwise.
+    pub fn load_ram(&mut self, data: &[u8]) -> Result<(), LoadError> {
+        if data.len() != self.ram.len() {
+            return Err(LoadError::WrongSize { size: data.len(), expected: self.ram.len() });
+        }
+        self.ram.copy_from_slice(data);
+        return Ok(());
+    }
+
+    // Indices past 0xF don't correspond to a key and are ignored
     pub fn keypress(&mut self, idx: usize, pressed: bool) {
-        self.keys[idx] = pressed;
+        if idx < NUM_KEYS {
+            self.keys[idx] = pressed;
+        }
+    }
+
+    // False for indices past 0xF, like keypress ignores them
+    pub fn is_key_pressed(&self, idx: usize) -> bool {
+        return idx < NUM_KEYS && self.keys[idx];
+    }
+
+    // True only for the frame a key went down in: from the keypress, through
+    // the run_frame that first sees it held, until the next run_frame
+    // starts. Holding it doesn't keep reporting it.
+    pub fn key_just_pressed(&self, idx: usize) -> bool {
+        return idx < NUM_KEYS && self.keys[idx] && !(self.frame_keys[idx] && self.prev_keys[idx]);
+    }
+
+    // What EX9E and EXA1 see for a key, see QuirkConfig::edge_triggered_keys
+    fn key_for_skip(&self, idx: usize) -> bool {
+        if self.quirks.edge_triggered_keys {
+            return self.key_just_pressed(idx);
+        }
+        return self.keys[idx];
     }
 
-    pub fn load(&mut self, data: &[u8]) {
-        let start = START_ADDR as usize;
-        let end = (START_ADDR as usize) + data.len();
+    // Sets all 16 keys at once, bit n for key n, e.g. from a replay that
+    // stores input as one mask per frame
+    pub fn set_keys(&mut self, mask: u16) {
+        for (idx, key) in self.keys.iter_mut().enumerate() {
+            *key = mask & (1 << idx) != 0;
+        }
+    }
+
+    // The keys held down, in the same layout set_keys takes
+    pub fn keys_mask(&self) -> u16 {
+        let mut mask = 0;
+        for (idx, &key) in self.keys.iter().enumerate() {
+            if key {
+                mask |= 1 << idx;
+            }
+        }
+        return mask;
+    }
+
+    pub fn keypress_key(&mut self, key: Key, pressed: bool) {
+        self.keypress(key.into(), pressed);
+    }
+
+    pub fn load(&mut self, data: &[u8]) -> Result<(), LoadError> {
+        if data.is_empty() {
+            return Err(LoadError::Empty);
+        }
+        self.load_at(self.start_addr, data)?;
+        self.rom_len = data.len();
+        return Ok(());
+    }
+
+    // (address, opcode) of every word in the loaded program that would fault
+    // as UnknownOpcode, to warn about ROMs for other variants up front.
+    // Sprites and other data in the ROM get scanned like code, so this
+    // over-reports: treat the results as hints, not errors.
+    pub fn scan_opcodes(&self) -> Vec<(u16, u16)> {
+        let start = self.start_addr as usize;
+        let mut unknown = Vec::new();
+        let mut addr = start;
+        while addr < start + self.rom_len {
+            let op = self.read_word(addr);
+            let at = addr as u16;
+            // F000's address word isn't an opcode of its own
+            addr += if decode(op) == Instruction::LoadLongI { 4 } else { 2 };
+            if let Instruction::Unknown(op) = decode(op) {
+                if op & 0xF000 == 0 && self.quirks.machine_routine_nop {
+                    continue;
+                }
+                unknown.push((at, op));
+            }
+        }
+        return unknown;
+    }
+
+    // Copies data anywhere in RAM, e.g. sprite tables or patches. Nothing is
+    // written unless all of it fits.
+    pub fn load_at(&mut self, addr: u16, data: &[u8]) -> Result<(), LoadError> {
+        let start = addr as usize;
+        let max = self.ram.len().saturating_sub(start);
+        // Even an empty block has to start inside RAM, or at its very end
+        if data.len() > max || start > self.ram.len() {
+            return Err(LoadError::TooLarge { size: data.len(), max });
+        }
+
+        let end = start + data.len();
         self.ram[start..end].copy_from_slice(data);
+        return Ok(());
     }
 
+    // load_at for several (address, data) blocks at once, e.g. a program
+    // and the sprite tables it expects. Everything is checked before
+    // anything is written, so on an error RAM is left as it was.
+    pub fn load_segments(&mut self, segments: &[(u16, &[u8])]) -> Result<(), LoadError> {
+        for (idx, &(addr, data)) in segments.iter().enumerate() {
+            let start = addr as usize;
+            let max = self.ram.len().saturating_sub(start);
+            // As in load_at, even an empty block has to start inside RAM
+            if data.len() > max || start > self.ram.len() {
+                return Err(LoadError::TooLarge { size: data.len(), max });
+            }
+
+            let end = start + data.len();
+            for &(other, other_data) in &segments[..idx] {
+                let (other_start, other_end) = (other as usize, other as usize + other_data.len());
+                if start < other_end && other_start < end {
+                    return Err(LoadError::Overlap { first: other, second: addr });
+                }
+            }
+        }
+
+        // All checked above, so these writes can't fail
+        for &(addr, data) in segments {
+            let start = addr as usize;
+            self.ram[start..start + data.len()].copy_from_slice(data);
+        }
+        return Ok(());
+    }
+
+    // Replaces the small font used by FX29. Glyphs are 5 bytes each, one
+    // byte per row, so digit n starts at byte 5 * n; a font shorter than
+    // FONTSET_SIZE only replaces the first glyphs. The font stays in place
+    // across resets.
+    pub fn set_fontset(&mut self, font: &[u8]) -> Result<(), LoadError> {
+        if font.len() > FONTSET_SIZE {
+            return Err(LoadError::TooLarge { size: font.len(), max: FONTSET_SIZE });
+        }
+
+        self.fontset[..font.len()].copy_from_slice(font);
+        self.ram[..font.len()].copy_from_slice(font);
+        return Ok(());
+    }
+
+    // Reads a ROM file and loads it. Too large a ROM comes back as InvalidData.
+    #[cfg(feature = "std")]
+    pub fn load_from_path<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let data = std::fs::read(path)?;
+        return self.load(&data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    }
+
+    // Quirks are configuration, not machine state, so they survive a reset
     pub fn reset(&mut self) {
-        self.pc = START_ADDR;
-        self.ram = [0; RAM_SIZE];
-        self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        self.ram.fill(0);
+        self.rom_len = 0;
+        self.ram[..FONTSET_SIZE].copy_from_slice(&self.fontset);
+        self.ram[BIG_FONT_ADDR..BIG_FONT_ADDR + BIG_FONTSET_SIZE].copy_from_slice(&BIG_FONTSET);
+        self.soft_reset();
+    }
+
+    // Like reset, but leaves RAM as it is, so the loaded program can be
+    // restarted without loading it again
+    pub fn soft_reset(&mut self) {
+        self.pc = self.start_addr;
+        self.plane_mask = 1;
+        self.hi_res = false;
+        self.clear_screen();
         self.v_reg = [0; NUM_REGS];
         self.i_reg = 0;
         self.sp = 0;
         self.stack = [0; STACK_SIZE];
+        self.flags = [0; NUM_FLAGS];
         self.keys = [false; NUM_KEYS];
+        self.frame_keys = [false; NUM_KEYS];
+        self.prev_keys = [false; NUM_KEYS];
+        self.wait_key = None;
         self.delay_t = 0;
         self.sound_t = 0;
-        self.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET)
+        self.halted = false;
+        self.idle = false;
+        self.last_draw_collisions = 0;
+        self.last_draw_bounds = None;
+        self.vblank_wait = false;
+        self.cycles = 0;
+        self.frames = 0;
+        self.cycle_remainder = 0;
+        self.timer_remainder = 0;
+        self.opcode_counts = [0; 16];
+        self.resume_from = None;
+        self.frame_ticks_left = None;
+        self.history.clear();
+        self.hooks.executed.fill(false);
+        self.audio_phase = 0.0;
+        self.audio_pattern = [0; AUDIO_PATTERN_SIZE];
+        self.pattern_loaded = false;
+        self.pitch = DEFAULT_PITCH;
     }
 
     // 1. Fetch
     // 2. Decode
     // 3. Execute
     // 4. Next instruction, back to 1.
+    // Breakpoints only stop step, tick_checked and run_frames: tick, and so
+    // run_frame, runs the instruction at one straight away.
     pub fn tick(&mut self) {
-        let op = self.fetch();
-        self.decode_and_execute(op);
+        if self.paused {
+            return;
+        }
+        match self.step() {
+            Ok(_) | Err(ExecError::Halted) => (),
+            // step has marked it as reported, so this runs it
+            Err(ExecError::Breakpoint(_)) => self.tick(),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Same as tick, but reports faults in the running program instead of panicking
+    pub fn tick_checked(&mut self) -> Result<(), ExecError> {
+        if self.paused {
+            return Ok(());
+        }
+        return self.step().map(|_| ());
+    }
+
+    // tick_checked under the Fault name, for frontends that route every
+    // runtime error through one handler
+    pub fn try_tick(&mut self) -> Result<(), Fault> {
+        return self.tick_checked();
+    }
+
+    // Ticks until pred holds, giving up after max_cycles instructions.
+    // Returns whether pred was ever satisfied.
+    pub fn run_until<F: FnMut(&Emulator) -> bool>(&mut self, max_cycles: usize, mut pred: F) -> bool {
+        for _ in 0..max_cycles {
+            if pred(self) {
+                return true;
+            }
+            self.tick();
+        }
+        return pred(self);
+    }
+
+    // Runs one instruction and reports what it was, for stepping debuggers
+    pub fn step(&mut self) -> Result<ExecutedInstruction, ExecError> {
+        if self.halted {
+            return Err(ExecError::Halted);
+        }
+
+        let pc = self.pc;
+        if self.resume_from.take() != Some(pc) && self.breakpoints.contains(&pc) {
+            self.resume_from = Some(pc);
+            return Err(ExecError::Breakpoint(pc));
+        }
+
+        let opcode = self.fetch();
+        let instruction = decode(opcode);
+        // Execute fetches the operand itself, this just reports it
+        let operand = if instruction == Instruction::LoadLongI {
+            Some(self.read_word(self.pc as usize))
+        } else {
+            None
+        };
+        let executed = ExecutedInstruction { pc, opcode, operand };
+        if !self.hooks.executed.is_empty() {
+            let len = if operand.is_some() { 4 } else { 2 };
+            for offset in 0..len {
+                let addr = self.ram_addr(pc as usize + offset);
+                self.hooks.executed[addr] = true;
+            }
+        }
+        self.cycles += 1;
+        self.idle = false;
+        if let Some(hook) = self.hooks.trace.as_mut() {
+            hook(executed);
+        }
+        self.opcode_counts[(opcode >> 12) as usize] += 1;
+        self.execute(instruction)?;
+        return Ok(executed);
+    }
+
+    // step stops before running the instruction at a breakpoint, and
+    // runs it on the following call
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    // Installs a callback run with every instruction before it executes, as
+    // step would report it. Useful for execution logs and coverage maps.
+    pub fn set_trace_hook(&mut self, f: Box<dyn FnMut(ExecutedInstruction) + Send>) {
+        self.hooks.trace = Some(f);
+    }
+
+    pub fn clear_trace_hook(&mut self) {
+        self.hooks.trace = None;
+    }
+
+    // Installs a callback run with the address whenever FX33 or FX55 writes
+    // over an instruction that has already run, i.e. self-modifying code.
+    // Only instructions run after the hook is set count.
+    pub fn set_smc_hook(&mut self, f: Box<dyn FnMut(u16) + Send>) {
+        self.hooks.smc = Some(f);
+        self.hooks.executed = vec![false; self.ram.len()];
+    }
+
+    pub fn clear_smc_hook(&mut self) {
+        self.hooks.smc = None;
+        self.hooks.executed = Vec::new();
+    }
+
+    // One 60Hz frame: clock_hz / 60 instructions, then the timers. Leftover
+    // cycles carry over, so 60 frames run exactly clock_hz instructions.
+    pub fn run_frame(&mut self) {
+        if self.paused {
+            return;
+        }
+        self.save_rewind_point();
+        let ticks = self.start_frame();
+        for _ in 0..ticks {
+            self.tick();
+            if self.vblank_wait {
+                break;
+            }
+        }
+        self.tick_timers();
+        self.frames += 1;
+    }
+
+    // Fast-forward: n frames back to back, as run_frame would run them but
+    // without saving rewind points. Stops partway through a frame at a
+    // breakpoint, exit or other fault and returns it; calling again carries
+    // on from there, finishing that frame before starting the next.
+    pub fn run_frames(&mut self, n: usize) -> Result<(), ExecError> {
+        if self.paused {
+            return Ok(());
+        }
+        for _ in 0..n {
+            let ticks = self.start_frame();
+            for done in 0..ticks {
+                let cycles = self.cycles;
+                if let Err(e) = self.step() {
+                    // A fault still used up its instruction, a breakpoint
+                    // or a halted machine didn't run one
+                    let ran = (self.cycles != cycles) as u32;
+                    self.frame_ticks_left = Some(ticks - done - ran);
+                    return Err(e);
+                }
+                if self.vblank_wait {
+                    break;
+                }
+            }
+            self.tick_timers();
+            self.frames += 1;
+        }
+        return Ok(());
+    }
+
+    // run_frame, then sleeps out whatever is left of the frame's 1/60s so
+    // calling this in a loop runs at real speed
+    #[cfg(feature = "std")]
+    pub fn run_frame_timed(&mut self) {
+        let start = Instant::now();
+        self.run_frame();
+        let budget = Duration::from_secs(1) / TIMER_HZ;
+        if let Some(rest) = budget.checked_sub(start.elapsed()) {
+            std::thread::sleep(rest);
+        }
+    }
+
+    pub fn snapshot(&self) -> EmulatorState {
+        return EmulatorState {
+            pc: self.pc,
+            i: self.i_reg,
+            sp: self.sp,
+            v_reg: self.v_reg,
+            stack: self.stack,
+            delay_timer: self.delay_t,
+            sound_timer: self.sound_t,
+            hi_res: self.hi_res,
+            screen: self.get_display(),
+        };
+    }
+
+    // Puts back a snapshot, unless its registers fail the same checks as
+    // validate, in which case nothing changes. A screen longer than the
+    // buffer is cut short.
+    pub fn restore(&mut self, state: &EmulatorState) -> Result<(), StateError> {
+        self.check_registers(state.pc, state.sp, &state.stack)?;
+        self.pc = state.pc;
+        self.i_reg = state.i;
+        self.sp = state.sp;
+        self.v_reg = state.v_reg;
+        self.stack = state.stack;
+        self.delay_t = state.delay_timer;
+        self.sound_t = state.sound_timer;
+        self.hi_res = state.hi_res;
+
+        self.screen = [0; PLANE_WORDS];
+        for (idx, &pixel) in state.screen.iter().take(SCREEN_BUF_SIZE).enumerate() {
+            if pixel {
+                self.screen[idx / 64] |= 1 << (idx % 64);
+            }
+        }
+        self.mark_all_dirty();
+        return Ok(());
+    }
+
+    // Checks the registers all point somewhere sensible, e.g. after restore
+    // or deserializing a save, so a bad state fails here instead of
+    // panicking partway through a tick. I isn't checked: everything it
+    // points at wraps around RAM, so no value of it can fault.
+    pub fn validate(&self) -> Result<(), StateError> {
+        let ram_size = self.ram.len();
+        if !(RAM_SIZE..=MAX_RAM_SIZE).contains(&ram_size) {
+            return Err(StateError::RamSize(ram_size));
+        }
+        return self.check_registers(self.pc, self.sp, &self.stack);
+    }
+
+    fn check_registers(&self, pc: u16, sp: u16, stack: &[u16; STACK_SIZE]) -> Result<(), StateError> {
+        let ram_size = self.ram.len();
+        if pc as usize >= ram_size {
+            return Err(StateError::PcOutOfRange(pc));
+        }
+        if sp as usize > STACK_SIZE {
+            return Err(StateError::SpOutOfRange(sp));
+        }
+        for &addr in &stack[..sp as usize] {
+            if addr as usize >= ram_size {
+                return Err(StateError::ReturnOutOfRange(addr));
+            }
+        }
+        return Ok(());
+    }
+
+    // Keeps the state from the start of each of the last `frames` run_frame
+    // calls (up to ten seconds' worth), or turns rewinding off with 0
+    pub fn enable_rewind(&mut self, frames: usize) {
+        self.rewind_frames = frames.min(MAX_REWIND_FRAMES);
+        while self.history.len() > self.rewind_frames {
+            self.history.pop_front();
+        }
+    }
+
+    // Undoes the most recent run_frame. Returns false once the history is used up.
+    pub fn rewind(&mut self) -> bool {
+        let point = match self.history.pop_back() {
+            Some(point) => point,
+            None => return false,
+        };
+
+        self.pc = point.pc;
+        self.ram = point.ram;
+        self.screen = point.screen;
+        self.screen_2 = point.screen_2;
+        self.plane_mask = point.plane_mask;
+        self.hi_res = point.hi_res;
+        self.last_draw_collisions = point.last_draw_collisions;
+        self.last_draw_bounds = point.last_draw_bounds;
+        self.vblank_wait = point.vblank_wait;
+        self.v_reg = point.v_reg;
+        self.i_reg = point.i_reg;
+        self.sp = point.sp;
+        self.stack = point.stack;
+        self.flags = point.flags;
+        self.frame_keys = point.frame_keys;
+        self.prev_keys = point.prev_keys;
+        self.wait_key = point.wait_key;
+        self.delay_t = point.delay_t;
+        self.sound_t = point.sound_t;
+        self.halted = point.halted;
+        self.idle = point.idle;
+        self.cycles = point.cycles;
+        self.frames = point.frames;
+        self.opcode_counts = point.opcode_counts;
+        self.cycle_remainder = point.cycle_remainder;
+        self.timer_remainder = point.timer_remainder;
+        self.audio_phase = point.audio_phase;
+        self.audio_pattern = point.audio_pattern;
+        self.pattern_loaded = point.pattern_loaded;
+        self.pitch = point.pitch;
+        self.rng = point.rng;
+        self.resume_from = None;
+        self.frame_ticks_left = None;
+        self.mark_all_dirty();
+        return true;
+    }
+
+    fn save_rewind_point(&mut self) {
+        if self.rewind_frames == 0 {
+            return;
+        }
+
+        if self.history.len() == self.rewind_frames {
+            self.history.pop_front();
+        }
+        self.history.push_back(RewindPoint {
+            pc: self.pc,
+            ram: self.ram.clone(),
+            screen: self.screen,
+            screen_2: self.screen_2,
+            plane_mask: self.plane_mask,
+            hi_res: self.hi_res,
+            last_draw_collisions: self.last_draw_collisions,
+            last_draw_bounds: self.last_draw_bounds,
+            vblank_wait: self.vblank_wait,
+            v_reg: self.v_reg,
+            i_reg: self.i_reg,
+            sp: self.sp,
+            stack: self.stack,
+            flags: self.flags,
+            frame_keys: self.frame_keys,
+            prev_keys: self.prev_keys,
+            wait_key: self.wait_key,
+            delay_t: self.delay_t,
+            sound_t: self.sound_t,
+            halted: self.halted,
+            idle: self.idle,
+            cycles: self.cycles,
+            frames: self.frames,
+            opcode_counts: self.opcode_counts,
+            cycle_remainder: self.cycle_remainder,
+            timer_remainder: self.timer_remainder,
+            audio_phase: self.audio_phase,
+            audio_pattern: self.audio_pattern,
+            pattern_loaded: self.pattern_loaded,
+            pitch: self.pitch,
+            rng: self.rng.clone(),
+        });
+    }
+
+    // Instructions per second run by run_frame, 700 by default
+    pub fn set_clock_hz(&mut self, hz: u32) {
+        self.clock_hz = hz;
+    }
+
+    pub fn get_clock_hz(&self) -> u32 {
+        return self.clock_hz;
+    }
+
+    // Gets the coming frame going and says how many instructions it runs:
+    // what was left of an interrupted one, or a whole new frame's worth
+    fn start_frame(&mut self) -> u32 {
+        // A DRAW run by tick between frames doesn't hold up this one
+        self.vblank_wait = false;
+        match self.frame_ticks_left.take() {
+            Some(ticks) => return ticks,
+            None => {
+                self.prev_keys = self.frame_keys;
+                self.frame_keys = self.keys;
+                return self.ticks_per_frame();
+            },
+        }
+    }
+
+    fn ticks_per_frame(&mut self) -> u32 {
+        let cycles = self.cycle_remainder as u64 + self.clock_hz as u64;
+        self.cycle_remainder = (cycles % TIMER_HZ as u64) as u32;
+        return (cycles / TIMER_HZ as u64) as u32;
     }
 
     pub fn tick_timers(&mut self) {
+        if self.paused {
+            return;
+        }
         if self.delay_t > 0 {
             self.delay_t -= 1;
         }
 
+        // The beep plays for as long as the sound timer is running, see is_beeping
         if self.sound_t > 0 {
-            if self.sound_t == 1 {
-                // Sound emitted
-            }
             self.sound_t -= 1;
         }
     }
 
+    // For frontends whose frames aren't 60Hz: counts the timers down by as
+    // many 60Hz ticks as fit in `dt_ms`, carrying the rest over to the next
+    // call, so beeps and delays last as long as they would on the real thing
+    pub fn tick_timers_dt(&mut self, dt_ms: u32) {
+        if self.paused {
+            return;
+        }
+        let elapsed = self.timer_remainder as u64 + dt_ms as u64 * TIMER_HZ as u64;
+        self.timer_remainder = (elapsed % 1000) as u32;
+        let ticks = (elapsed / 1000).min(u8::MAX as u64) as u8;
+
+        self.delay_t = self.delay_t.saturating_sub(ticks);
+        self.sound_t = self.sound_t.saturating_sub(ticks);
+    }
+
+    // Frontends poll this each frame to start and stop their beep
+    pub fn is_beeping(&self) -> bool {
+        return self.sound_t > 0 && !self.paused;
+    }
+
+    // Fills `buf` with mono samples while the sound timer runs, silence
+    // otherwise or at a sample rate of 0.
+    // That's a square wave, or the XO-CHIP audio pattern once a program has
+    // loaded one. The phase carries over between calls so consecutive
+    // buffers join up without clicks.
+    pub fn fill_audio(&mut self, buf: &mut [f32], sample_rate: u32) {
+        if !self.is_beeping() || sample_rate == 0 {
+            buf.fill(0.0);
+            return;
+        }
+
+        // Step through one square wave period, or the whole 128 bit pattern
+        let step = if self.pattern_loaded {
+            self.pattern_rate() / (AUDIO_PATTERN_SIZE * 8) as f32 / sample_rate as f32
+        } else {
+            self.tone_hz / sample_rate as f32
+        };
+
+        for sample in buf.iter_mut() {
+            let high = if self.pattern_loaded {
+                let bit = (self.audio_phase * (AUDIO_PATTERN_SIZE * 8) as f32) as usize;
+                self.audio_pattern[bit / 8] & (0b1000_0000 >> (bit % 8)) != 0
+            } else {
+                self.audio_phase < 0.5
+            };
+            *sample = if high { BEEP_AMPLITUDE } else { -BEEP_AMPLITUDE };
+            // A step can be longer than a whole period when the sample
+            // rate is low, so wrap rather than just subtracting 1.0
+            self.audio_phase = (self.audio_phase + step) % 1.0;
+        }
+    }
+
+    // Pitch of the beep produced by fill_audio, 440Hz by default
+    pub fn set_tone_frequency(&mut self, hz: f32) {
+        self.tone_hz = hz;
+    }
+
+    pub fn get_audio_pattern(&self) -> &[u8; AUDIO_PATTERN_SIZE] {
+        return &self.audio_pattern;
+    }
+
+    // Bits per second for the pattern, set by FX3A
+    fn pattern_rate(&self) -> f32 {
+        return 4000.0 * libm::exp2f((self.pitch as f32 - 64.0) / 48.0);
+    }
+
     fn fetch(&mut self) -> u16 {
         // Opcodes are 2 bytes
         // But RAM is a byte wide
         // So fetch 2 bytes and concat them in Big Endian u16
-        let upper_byte = self.ram[self.pc as usize] as u16;
-        let lower_byte = self.ram[(self.pc + 1) as usize] as u16;
-        let op = (upper_byte << 8) | lower_byte;
-        self.pc += 2;
+        let pc = self.pc as usize;
+        let op = self.read_word(pc);
+        self.pc = self.wrap_pc(pc + 2);
         return op
     }
 
-    fn decode_and_execute(&mut self, op: u16) {
-        let hex_1 = (op & 0xF000) >> 12;
-        let hex_2 = (op & 0x0F00) >> 8;
-        let hex_3 = (op & 0x00F0) >> 4;
-        let hex_4 = op & 0x000F;
+    fn read_word(&self, addr: usize) -> u16 {
+        let upper_byte = self.ram[self.ram_addr(addr)] as u16;
+        let lower_byte = self.ram[self.ram_addr(addr + 1)] as u16;
+        return (upper_byte << 8) | lower_byte;
+    }
 
-        match (hex_1, hex_2, hex_3, hex_4) {
+    // Runs an already decoded instruction. PC should already point past it,
+    // as it does after fetch.
+    pub fn execute(&mut self, instr: Instruction) -> Result<(), ExecError> {
+        match instr {
             // NOP
-            (0, 0, 0, 0) => return,
+            Instruction::Nop => return Ok(()),
 
             // CLS
-            (0, 0, 0xE, 0) => {
-                self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+            // Only clears the planes selected by FN01. The default mask is
+            // plane 0 alone, which is the whole screen outside XO-CHIP.
+            Instruction::Cls => {
+                self.clear_planes(self.plane_mask);
             },
 
             // RET
-            (0, 0, 0xE, 0xE) => {
-                let ret_addr = self.pop();
+            Instruction::Ret => {
+                let ret_addr = self.pop()?;
                 self.pc = ret_addr;
             },
 
+            // SCROLL DOWN N
+            // All the scrolls move by pixels of the active resolution
+            Instruction::ScrollDown { n } => {
+                self.scroll_down(n as usize);
+            },
+
+            // SCROLL UP N
+            // XO-CHIP's counterpart to SCROLL DOWN
+            Instruction::ScrollUp { n } => {
+                self.scroll_up(n as usize);
+            },
+
+            // SCROLL RIGHT 4
+            Instruction::ScrollRight => {
+                self.scroll_right(4);
+            },
+
+            // SCROLL LEFT 4
+            Instruction::ScrollLeft => {
+                self.scroll_left(4);
+            },
+
+            // EXIT
+            Instruction::Exit => {
+                self.halted = true;
+            },
+
+            // LOW RES
+            Instruction::LowRes => {
+                self.hi_res = false;
+                self.clear_screen();
+            },
+
+            // HIGH RES
+            Instruction::HighRes => {
+                self.hi_res = true;
+                self.clear_screen();
+            },
+
             // JMP NNN
-            (1, _, _, _) => {
-                let nnn = op & 0xFFF; // Does this drop the last byte?
+            Instruction::Jump { nnn } => {
+                // Games often finish by spinning on a jump to the same instruction
+                self.idle = self.wrap_pc(nnn as usize + 2) == self.pc;
                 self.pc = nnn;
             },
 
             // CALL NNN
-            (2, _, _, _) => {
-                let nnn = op & 0xFFF;
-                self.push(self.pc);
+            Instruction::Call { nnn } => {
+                self.push(self.pc)?;
                 self.pc = nnn;
             },
 
             // SKIP VX == NN
-            (3, _, _, _) => {
-                let x = hex_2 as usize;
-                let nn = (op & 0xFF) as u8;
-                if self.v_reg[x] == nn {
-                    self.pc += 2; //each opcode is 2 bytes
+            Instruction::SkipEqImm { x, nn } => {
+                if self.v_reg[x as usize] == nn {
+                    self.skip();
                 }
             },
 
             // SKIP VX != NN
-            (4, _, _, _) => {
-                let x = hex_2 as usize;
-                let nn = (op & 0xFF) as u8;
-                if self.v_reg[x] != nn {
-                    self.pc += 2;
+            Instruction::SkipNeImm { x, nn } => {
+                if self.v_reg[x as usize] != nn {
+                    self.skip();
                 }
             },
 
             // SKIP VX == VY
-            (5, _, _, 0) => {
-                let x = hex_2 as usize;
-                let y = hex_3 as usize;
-                if self.v_reg[x] == self.v_reg[y] {
-                    self.pc += 2;
+            Instruction::SkipEqReg { x, y } => {
+                if self.v_reg[x as usize] == self.v_reg[y as usize] {
+                    self.skip();
                 }
             },
 
             // VX := NN
-            (6, _, _, _) => {
-                let x = hex_2 as usize;
-                let nn = (op & 0xFF) as u8;
-                self.v_reg[x] = nn;
+            Instruction::LoadImm { x, nn } => {
+                self.v_reg[x as usize] = nn;
             },
             
             // VX += NN
-            (7, _, _, _) => {
-                let x = hex_2 as usize;
-                let nn = (op & 0xFF) as u8;
-                self.v_reg[x] = self.v_reg[x].wrapping_add(nn); // What does wrapping add do?
+            // Wraps around past 0xFF, and unlike 8XY4 there's no carry:
+            // VF only changes when it is VX
+            Instruction::AddImm { x, nn } => {
+                let x = x as usize;
+                self.v_reg[x] = self.v_reg[x].wrapping_add(nn);
             },
 
             // VX := VY
-            (8, _, _, 0) => {
-                let x = hex_2 as usize;
-                let y = hex_3 as usize;
-                self.v_reg[x] = self.v_reg[y];
+            Instruction::Move { x, y } => {
+                self.v_reg[x as usize] = self.v_reg[y as usize];
             },
 
             // VX |= VY
-            (8, _, _, 1) => {
-                let x = hex_2 as usize;
-                let y = hex_3 as usize;
-                self.v_reg[x] |= self.v_reg[y];
+            Instruction::Or { x, y } => {
+                self.v_reg[x as usize] |= self.v_reg[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.v_reg[0xF] = 0;
+                }
             },
 
             // VX &= VY
-            (8, _, _, 2) => {
-                let x = hex_2 as usize;
-                let y = hex_3 as usize;
-                self.v_reg[x] &= self.v_reg[y]; 
+            Instruction::And { x, y } => {
+                self.v_reg[x as usize] &= self.v_reg[y as usize]; 
+                if self.quirks.logic_resets_vf {
+                    self.v_reg[0xF] = 0;
+                }
             },
 
             // VX ^= VY
-            (8, _, _, 3) => {
-                let x = hex_2 as usize;
-                let y = hex_3 as usize;
-                self.v_reg[x] ^= self.v_reg[y];
+            Instruction::Xor { x, y } => {
+                self.v_reg[x as usize] ^= self.v_reg[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.v_reg[0xF] = 0;
+                }
             }
 
             // VX += VY
-            (8, _, _, 4) => {
-                let x = hex_2 as usize;
-                let y = hex_3 as usize;
+            // This and the other flag-setting 8XYN ops read both operands
+            // before writing anything, then write VX and VF last. So with VF
+            // as VX or VY the result is computed from its old value, and
+            // with VF as VX the flag is what's left in it.
+            Instruction::Add { x, y } => {
+                let x = x as usize;
+                let y = y as usize;
 
                 let (new_vx, carry) = self.v_reg[x].overflowing_add(self.v_reg[y]);
                 let new_vf = if carry { 1 } else { 0 };
@@ -244,9 +1832,9 @@ impl Emulator {
             },
 
             // VX -= VY
-            (8, _, _, 5) => {
-                let x = hex_2 as usize;
-                let y = hex_3 as usize;
+            Instruction::Sub { x, y } => {
+                let x = x as usize;
+                let y = y as usize;
 
                 let (new_vx, borrow) = self.v_reg[x].overflowing_sub(self.v_reg[y]);
                 let new_vf = if borrow { 0 } else { 1 };
@@ -256,17 +1844,20 @@ impl Emulator {
             },
 
             // VX >>= 1
-            (8, _, _, 6) => {
-                let x = hex_2 as usize;
+            Instruction::ShiftRight { x, y } => {
+                let x = x as usize;
+                if self.quirks.shift_uses_vy {
+                    self.v_reg[x] = self.v_reg[y as usize];
+                }
                 let lsb = self.v_reg[x] & 1;
                 self.v_reg[x] >>= 1;
                 self.v_reg[0xF] = lsb;
             },
 
             // VX := VY - VX
-            (8, _, _, 7) => {
-                let x = hex_2 as usize;
-                let y = hex_2 as usize;
+            Instruction::SubN { x, y } => {
+                let x = x as usize;
+                let y = y as usize;
 
                 let (new_vx, borrow) = self.v_reg[y].overflowing_sub(self.v_reg[x]);
                 let new_vf = if borrow { 0 } else { 1 };
@@ -276,78 +1867,75 @@ impl Emulator {
             },
 
             // VX <<= 1
-            (8, _, _, 0xE) => {
-                let x = hex_2 as usize;
+            Instruction::ShiftLeft { x, y } => {
+                let x = x as usize;
+                if self.quirks.shift_uses_vy {
+                    self.v_reg[x] = self.v_reg[y as usize];
+                }
                 let msb = self.v_reg[x] >> 7;
                 self.v_reg[x] <<= 1;
                 self.v_reg[0xF] = msb;
             },
 
             // SKIP VX != VY
-            (9, _, _, 0) => {
-                let x = hex_2 as usize;
-                let y = hex_3 as usize;
-                if self.v_reg[x] != self.v_reg[y] {
-                    self.pc += 2;
+            Instruction::SkipNeReg { x, y } => {
+                if self.v_reg[x as usize] != self.v_reg[y as usize] {
+                    self.skip();
                 }
             },
 
             // I := NNN
-            (0xA, _, _, _) => {
-                let nnn = op & 0xFFF;
+            Instruction::LoadI { nnn } => {
                 self.i_reg = nnn;
             },
 
             // JMP V0 + NNN
-            (0xB, _, _, _) => {
-                let nnn = op & 0xFFF;
-                self.pc = (self.v_reg[0] as u16) + nnn;
+            // SUPER-CHIP reads this as BXNN, jumping to XNN + VX
+            Instruction::JumpOffset { x, nnn } => {
+                let offset = if self.quirks.jump_with_offset_vx {
+                    self.v_reg[x as usize]
+                } else {
+                    self.v_reg[0]
+                };
+                self.pc = self.wrap_pc(offset as usize + nnn as usize);
             },
 
             // VX := rand() & NN
-            (0xC, _, _, _) => {
-                let x = hex_2 as usize;
-                let nn = (op & 0xFF) as u8;
-                let rng: u8 = random();
-                self.v_reg[x] = rng & nn;
+            Instruction::Random { x, nn } => {
+                let rng = self.rng.next_byte();
+                self.v_reg[x as usize] = rng & nn;
             },
 
             // DRAW
-            (0xD, _, _, _) => {
+            Instruction::Draw { x, y, n } => {
                 // Get the (x, y) coords for our sprite
-                let x_coord = self.v_reg[hex_2 as usize] as u16;
-                let y_coord = self.v_reg[hex_3 as usize] as u16;
+                let x_coord = self.v_reg[x as usize];
+                let y_coord = self.v_reg[y as usize];
 
                 // Last digit gets sprite height 
-                let num_rows = hex_4;
+                // SUPER-CHIP uses 0 for a 16x16 sprite, stored as 2 bytes per row
+                let num_rows = self.draw_height(n);
+                let sprite_width = if n == 0 { 16 } else { 8 };
+                let bytes_per_row = sprite_width / 8;
 
-                let mut flipped = false; 
-
-                for y_line in 0..num_rows {
-                    // Figure out where the row data is stored 
-                    let addr = self.i_reg + y_line as u16;
-                    let pixels = self.ram[addr as usize];
-
-                    // Iterate over each column in our row 
-                    for x_line in 0..8 {
-                        // Pixel mask
-                        if (pixels & (0b1000_0000 >> x_line)) != 0 {
-                            // Sprites wrap around screen 
-                            let x = (x_coord + x_line) as usize % SCREEN_WIDTH;
-                            let y = (y_coord + y_line) as usize % SCREEN_HEIGHT;
-
-                            // Get the pixel index
-                            let idx = x + SCREEN_WIDTH * y;
-                            
-                            // Check if we're about to flip, and set 
-                            flipped |= self.screen[idx];
-                            self.screen[idx] ^= true;
-                        }
+                // With several planes selected, each gets its own copy of the
+                // sprite data, one after the other starting at I
+                let i = self.i_reg as usize;
+                let (collided, rows_hit) = self.draw_rows(x_coord, y_coord, num_rows, sprite_width, |emu, row| {
+                    // Rows are read into the top of a u16 so both widths share one mask
+                    let addr = i + row * bytes_per_row;
+                    let mut pixels = (emu.ram[emu.ram_addr(addr)] as u16) << 8;
+                    if sprite_width == 16 {
+                        pixels |= emu.ram[emu.ram_addr(addr + 1)] as u16;
                     }
-                }
+                    return pixels;
+                });
+                self.vblank_wait = self.quirks.display_wait;
 
                 // Populate VF register
-                if flipped {
+                if self.hi_res && self.quirks.schip_collision_count {
+                    self.v_reg[0xF] = rows_hit as u8;
+                } else if collided {
                     self.v_reg[0xF] = 1;
                 } else {
                     self.v_reg[0xF] = 0;
@@ -355,129 +1943,466 @@ impl Emulator {
             },
 
             // SKIP KEY PRESS
-            (0xE, _, 9, 0xE) => {
-                let x = hex_2 as usize;
-                let vx = self.v_reg[x];
-                let key = self.keys[vx as usize];
+            Instruction::SkipKey { x } => {
+                // Only the low nibble names a key
+                let vx = self.v_reg[x as usize] & 0xF;
+                let key = self.key_for_skip(vx as usize);
                 if key {
-                    self.pc += 2;
+                    self.skip();
                 }
             },
 
             // SKIP IF KEY NOT PRESSED
-            (0xE, _, 0xA, 1) => {
-                let x = hex_2 as usize;
-                let vx = self.v_reg[x];
-                let key = self.keys[vx as usize];
+            Instruction::SkipNotKey { x } => {
+                // Only the low nibble names a key
+                let vx = self.v_reg[x as usize] & 0xF;
+                let key = self.key_for_skip(vx as usize);
                 if !key {
-                    self.pc += 2;
+                    self.skip();
                 }
             },
 
             // VX = DT
-            (0xF, _, 0, 7) => {
-                let x = hex_2 as usize;
-                self.v_reg[x] = self.delay_t;
+            Instruction::LoadDelay { x } => {
+                self.v_reg[x as usize] = self.delay_t;
             },
 
             // WAIT KEY
-            (0xF, _, 0, 0xA) => {
-                let x = hex_2 as usize;
-                let mut pressed = false; 
-                for i in 0..self.keys.len() {
-                    if self.keys[i] {
-                        self.v_reg[x] = i as u8;
-                        pressed = true; 
-                        break;
-                    }
+            // Like the VIP, waits for a key to be pressed and then released,
+            // so a held key doesn't satisfy several waits in a row
+            Instruction::WaitKey { x } => {
+                let mut released = false; 
+                match self.wait_key {
+                    None => {
+                        self.wait_key = self.keys.iter().position(|k| *k);
+                    },
+                    Some(k) => {
+                        if !self.keys[k] {
+                            self.v_reg[x as usize] = k as u8;
+                            self.wait_key = None;
+                            released = true;
+                        }
+                    },
                 }
 
                 // This OP is blocking
-                if !pressed {
+                if !released {
                     // redo opcode
-                    self.pc -= 2;
+                    self.pc = self.wrap_pc(self.pc as usize + self.ram.len() - 2);
                 }
             },
 
             // DT = VX
-            (0xF, _, 1, 5) => {
-                let x = hex_2 as usize;
-                self.delay_t = self.v_reg[x];
+            Instruction::SetDelay { x } => {
+                self.delay_t = self.v_reg[x as usize];
             },
 
             // ST = VX
-            (0xF, _, 1, 8) => {
-                let x = hex_2 as usize;
-                self.sound_t = self.v_reg[x];
+            Instruction::SetSound { x } => {
+                self.sound_t = self.v_reg[x as usize];
             },
 
             // I += VX
-            (0xF, _, 1, 0xE) => {
-                let x = hex_2 as usize;
-                let vx = self.v_reg[x] as u16;
+            Instruction::AddI { x } => {
+                let vx = self.v_reg[x as usize] as u16;
                 self.i_reg = self.i_reg.wrapping_add(vx);
             },
 
             // Set I = FONT
-            (0xF, _, 2, 9) => {
-                let x = hex_2 as usize;
-                let c = self.v_reg[x] as u16;
+            Instruction::LoadFont { x } => {
+                let c = self.v_reg[x as usize] as u16;
                 self.i_reg = c * 5;
             },
 
+            // AUDIO PATTERN = 16 bytes at I
+            Instruction::AudioPattern => {
+                let i = self.i_reg as usize;
+                for idx in 0..AUDIO_PATTERN_SIZE {
+                    self.audio_pattern[idx] = self.ram[self.ram_addr(i + idx)];
+                }
+                self.pattern_loaded = true;
+            },
+
+            // SELECT PLANES N
+            Instruction::SelectPlanes { n } => {
+                self.plane_mask = n & 0b11;
+            },
+
+            // PITCH = VX
+            Instruction::SetPitch { x } => {
+                self.pitch = self.v_reg[x as usize];
+            },
+
+            // Set I = BIG FONT
+            Instruction::LoadBigFont { x } => {
+                let c = (self.v_reg[x as usize] & 0xF) as u16;
+                self.i_reg = (BIG_FONT_ADDR as u16) + c * 10;
+            },
+
             // BCD 
-            (0xF, _, 3, 3) => {
-                let x = hex_2 as usize;
-                let vx = self.v_reg[x] as f32;
+            Instruction::Bcd { x } => {
+                let vx = self.v_reg[x as usize];
 
                 // Fetch hundreds digit 
-                let hundreds = (vx / 100.0).floor() as u8;
-                let tens = ((vx / 10.0)).floor() as u8; 
-                let ones = (vx % 10.0) as u8;
+                let hundreds = vx / 100;
+                let tens = (vx / 10) % 10; 
+                let ones = vx % 10;
 
-                self.ram[self.i_reg as usize] = hundreds; 
-                self.ram[(self.i_reg + 1) as usize] = tens; 
-                self.ram[(self.i_reg + 2) as usize] = ones;
+                let i = self.i_reg as usize;
+                for (idx, digit) in [hundreds, tens, ones].into_iter().enumerate() {
+                    self.write_ram(i + idx, digit);
+                }
             },
 
             // STORE VO - VX
-            (0xF, _, 5, 5) => {
-                let x = hex_2 as usize;
+            Instruction::Store { x } => {
+                let x = x as usize;
                 let i = self.i_reg as usize;
                 for idx in 0..=x {
-                    self.ram[i+idx] = self.v_reg[idx]
+                    self.write_ram(i + idx, self.v_reg[idx]);
+                }
+
+                // The VIP leaves I just past the last byte written
+                if self.quirks.load_store_increments_i {
+                    self.i_reg = self.i_reg.wrapping_add((x as u16) + 1);
                 }
             },
 
             // LOAD VO - VX
-            (0xF, _, 6, 5) => {
-                let x = hex_2 as usize; 
+            Instruction::Load { x } => {
+                let x = x as usize; 
                 let i = self.i_reg as usize; 
                 for idx in 0..=x {
-                    self.v_reg[idx] = self.ram[i + idx];
+                    self.v_reg[idx] = self.ram[self.ram_addr(i + idx)];
+                }
+
+                if self.quirks.load_store_increments_i {
+                    self.i_reg = self.i_reg.wrapping_add((x as u16) + 1);
                 }
             }
 
+            // FLAGS = V0 - VX
+            // There are only 8 flags, so like the HP-48 anything past V7 is left out
+            Instruction::StoreFlags { x } => {
+                let x = (x as usize).min(NUM_FLAGS - 1);
+                self.flags[..=x].copy_from_slice(&self.v_reg[..=x]);
+            },
+
+            // V0 - VX = FLAGS
+            Instruction::LoadFlags { x } => {
+                let x = (x as usize).min(NUM_FLAGS - 1);
+                self.v_reg[..=x].copy_from_slice(&self.flags[..=x]);
+            },
 
+            // LD I, LONG NNNN
+            // XO-CHIP's one 4-byte instruction: the address is the word after
+            // it. I keeps all 16 bits; it's only wrapped into RAM when used.
+            Instruction::LoadLongI => {
+                let addr = self.fetch();
+                self.i_reg = addr;
+            },
 
+            // SYS NNN
+            // Whatever 0NNN is left after the opcodes above
+            Instruction::Unknown(op) if op & 0xF000 == 0 && self.quirks.machine_routine_nop => return Ok(()),
 
+            Instruction::Unknown(op) => return Err(ExecError::UnknownOpcode(op)),
+        }
 
-            
+        return Ok(());
+    }
+
+    // XORs a sprite onto each selected plane, as DXYN does. `row_data(self, n)`
+    // gives row n in the top bits of a u16, numbered on through the planes,
+    // so plane 1's first row is n = num_rows. Records the collision count and
+    // bounds, and returns whether any pixel was turned off, along with how
+    // many rows either turned one off or were clipped off the bottom.
+    fn draw_rows<F>(&mut self, x_coord: u8, y_coord: u8, num_rows: usize, sprite_width: usize, row_data: F) -> (bool, usize)
+    where
+        F: Fn(&Self, usize) -> u16,
+    {
+        let (width, height) = self.display_dimensions();
+        let mut collisions = 0; 
+        let mut rows_hit = 0;
+
+        // The starting position always wraps; whether the rest of the
+        // sprite wraps or is clipped at the edges is a quirk
+        let x_coord = x_coord as usize % width;
+        let y_coord = y_coord as usize % height;
+
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        let (mut wrapped_x, mut wrapped_y) = (false, false);
+        let mut row = 0;
+        for plane in self.selected_planes() {
+            for y_line in 0..num_rows {
+                let pixels = row_data(self, row);
+                row += 1;
+                let mut row_collided = false;
+
+                // Iterate over each column in our row 
+                for x_line in 0..sprite_width {
+                    // Pixel mask
+                    if (pixels & (0x8000 >> x_line)) != 0 {
+                        let mut x = x_coord + x_line;
+                        let mut y = y_coord + y_line;
+                        if self.quirks.sprite_wrapping {
+                            // Sprites wrap around screen 
+                            wrapped_x |= x >= width;
+                            wrapped_y |= y >= height;
+                            x %= width;
+                            y %= height;
+                        } else if x >= width || y >= height {
+                            continue;
+                        }
+
+                        // Get the pixel index
+                        let idx = x + width * y;
+                        let bit = 1 << (idx % 64);
+                        
+                        // Check if we're about to flip, and set 
+                        let screen = self.plane_mut(plane);
+                        if screen[idx / 64] & bit != 0 {
+                            collisions += 1;
+                            row_collided = true;
+                        }
+                        screen[idx / 64] ^= bit;
+                        self.dirty_rows |= 1 << y;
+                        bounds = Some(match bounds {
+                            None => (x, y, x, y),
+                            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                        });
+                    }
+                }
 
+                let clipped = !self.quirks.sprite_wrapping && y_coord + y_line >= height;
+                if row_collided || clipped {
+                    rows_hit += 1;
+                }
+            }
+        }
+
+        self.draw_flag = true;
+        self.last_draw_collisions = collisions;
+        // A wrapped sprite is split across opposite edges, so it's
+        // reported as spanning the whole screen in that direction
+        self.last_draw_bounds = bounds.map(|(x0, y0, x1, y1)| {
+            let (x0, x1) = if wrapped_x { (0, width - 1) } else { (x0, x1) };
+            let (y0, y1) = if wrapped_y { (0, height - 1) } else { (y0, y1) };
+            (x0, y0, x1, y1)
+        });
+        return (collisions > 0, rows_hit);
+    }
+
+    // Addresses worked out from I wrap around the top of RAM rather than
+    // running off the end, as they do on real hardware
+    fn ram_addr(&self, addr: usize) -> usize {
+        return addr % self.ram.len();
+    }
+
+    // Stores a byte on behalf of the program, wrapping like ram_addr and
+    // reporting writes over code that has run to the SMC hook
+    fn write_ram(&mut self, addr: usize, val: u8) {
+        let addr = self.ram_addr(addr);
+        self.ram[addr] = val;
+        if self.hooks.executed.get(addr) == Some(&true) {
+            if let Some(hook) = self.hooks.smc.as_mut() {
+                hook(addr as u16);
+            }
+        }
+    }
+
+    // Steps PC over the next instruction for the skips, which means 4 bytes
+    // when that's an F000 NNNN
+    fn skip(&mut self) {
+        let pc = self.pc as usize;
+        let long = self.read_word(pc) == 0xF000;
+        self.pc = self.wrap_pc(pc + if long { 4 } else { 2 });
+    }
+
+    // PC wraps the same way, so execution carries on from address 0
+    fn wrap_pc(&self, pc: usize) -> u16 {
+        return self.ram_addr(pc) as u16;
+    }
+
+    fn plane(&self, plane: usize) -> &Plane {
+        if plane == 0 {
+            return &self.screen;
+        } else {
+            return &self.screen_2;
+        }
+    }
+
+    fn plane_mut(&mut self, plane: usize) -> &mut Plane {
+        if plane == 0 {
+            return &mut self.screen;
+        } else {
+            return &mut self.screen_2;
+        }
+    }
+
+    fn selected_planes(&self) -> impl Iterator<Item = usize> {
+        let mask = self.plane_mask;
+        return (0..NUM_PLANES).filter(move |plane| mask & (1 << plane) != 0);
+    }
+
+    fn clear_planes(&mut self, mask: u8) {
+        for plane in 0..NUM_PLANES {
+            if mask & (1 << plane) != 0 {
+                self.plane_mut(plane).fill(0);
+            }
+        }
+        self.mark_all_dirty();
+    }
+
+    fn mark_all_dirty(&mut self) {
+        let (_, height) = self.display_dimensions();
+        self.dirty_rows = u64::MAX >> (64 - height);
+        self.draw_flag = true;
+    }
+
+    // Rows are whole words, so vertical scrolls move words around
+    fn scroll_down(&mut self, n: usize) {
+        let (width, height) = self.display_dimensions();
+        self.mark_all_dirty();
+        let shift = n.min(height) * width / 64;
+        let end = width * height / 64;
+        for plane in self.selected_planes() {
+            let screen = self.plane_mut(plane);
+            screen.copy_within(0..(end - shift), shift);
+            screen[..shift].fill(0);
+        }
+    }
+
+    fn scroll_up(&mut self, n: usize) {
+        let (width, height) = self.display_dimensions();
+        self.mark_all_dirty();
+        let shift = n.min(height) * width / 64;
+        let end = width * height / 64;
+        for plane in self.selected_planes() {
+            let screen = self.plane_mut(plane);
+            screen.copy_within(shift..end, 0);
+            screen[end - shift..end].fill(0);
+        }
+    }
+
+    // Moving right is towards higher bits, so horizontal scrolls shift each
+    // row as one u128
+    fn scroll_right(&mut self, n: usize) {
+        self.scroll_rows(|row, n| row.checked_shl(n).unwrap_or(0), n);
+    }
+
+    fn scroll_left(&mut self, n: usize) {
+        self.scroll_rows(|row, n| row.checked_shr(n).unwrap_or(0), n);
+    }
 
-            // Exhaustive pattern matching in Rust
-            (_, _, _, _) => unimplemented!("Unimplemented opcode: {}", op),
+    fn scroll_rows<F: Fn(u128, u32) -> u128>(&mut self, shift: F, n: usize) {
+        let (width, height) = self.display_dimensions();
+        self.mark_all_dirty();
+        let words = width / 64;
+        let mask = u128::MAX >> (128 - width);
+        for plane in self.selected_planes() {
+            for row in self.plane_mut(plane)[..width * height / 64].chunks_mut(words) {
+                let bits = row.iter().rev().fold(0, |acc, &word| acc << 64 | word as u128);
+                let bits = shift(bits, n as u32) & mask;
+                for (idx, word) in row.iter_mut().enumerate() {
+                    *word = (bits >> (64 * idx)) as u64;
+                }
+            }
         }
     }
 
-    fn push(&mut self, val: u16) {
+    fn push(&mut self, val: u16) -> Result<(), ExecError> {
+        if self.sp as usize >= STACK_SIZE {
+            return Err(ExecError::StackOverflow);
+        }
+
         // Why 'as usize'?
         self.stack[self.sp as usize] = val;
         self.sp += 1;
+        return Ok(());
     }
 
-    fn pop(&mut self) -> u16 {
+    fn pop(&mut self) -> Result<u16, ExecError> {
+        if self.sp == 0 {
+            return Err(ExecError::StackUnderflow);
+        }
+        // Only a corrupt save can leave SP past the top, see validate
+        if self.sp as usize > STACK_SIZE {
+            return Err(ExecError::StackOverflow);
+        }
+
         self.sp -= 1;
-        return self.stack[self.sp as usize];
+        return Ok(self.stack[self.sp as usize]);
     }
-}
\ No newline at end of file
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subn_subtracts_vx_from_vy() {
+        // V1 := V2 - V1
+        let mut emulator = Emulator::new();
+        emulator.load(&[0x81, 0x27]).unwrap();
+        emulator.v_reg[1] = 5;
+        emulator.v_reg[2] = 9;
+        emulator.tick();
+        assert_eq!(emulator.v_reg[1], 4);
+        assert_eq!(emulator.v_reg[0xF], 1);
+    }
+
+    #[test]
+    fn subn_borrows_on_underflow() {
+        let mut emulator = Emulator::new();
+        emulator.load(&[0x81, 0x27]).unwrap();
+        emulator.v_reg[1] = 9;
+        emulator.v_reg[2] = 5;
+        emulator.tick();
+        assert_eq!(emulator.v_reg[1], 252);
+        assert_eq!(emulator.v_reg[0xF], 0);
+    }
+
+    #[test]
+    fn jump_offset_uses_v0_by_default() {
+        // JP V0, 0x210
+        let mut emulator = Emulator::new();
+        emulator.load(&[0xB2, 0x10]).unwrap();
+        emulator.v_reg[0] = 1;
+        emulator.v_reg[2] = 4;
+        emulator.tick();
+        assert_eq!(emulator.pc, 0x211);
+    }
+
+    #[test]
+    fn jump_offset_uses_vx_with_quirk() {
+        let quirks = QuirkConfig { jump_with_offset_vx: true, ..QuirkConfig::default() };
+        let mut emulator = Emulator::with_quirks(quirks);
+        emulator.load(&[0xB2, 0x10]).unwrap();
+        emulator.v_reg[0] = 1;
+        emulator.v_reg[2] = 4;
+        emulator.tick();
+        assert_eq!(emulator.pc, 0x214);
+    }
+
+    #[test]
+    fn same_seed_gives_same_random_numbers() {
+        // VX := rand() & 0xFF for every register
+        let rom: Vec<u8> = (0..16).flat_map(|x| [0xC0 | x, 0xFF]).collect();
+        let mut first = Emulator::with_rng(42);
+        let mut second = Emulator::with_rng(42);
+        first.load(&rom).unwrap();
+        second.load(&rom).unwrap();
+        for _ in 0..16 {
+            first.tick();
+            second.tick();
+        }
+        assert_eq!(first.v_reg, second.v_reg);
+    }
+}
@@ -0,0 +1,49 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+
+// A 16-bit Galois LFSR for CXNN, for replays that have to match byte for
+// byte whatever the host or rand version. It isn't the VIP's generator,
+// which reads bytes out of the interpreter ROM and depends on interrupt
+// timing, neither of which is emulated. A zero seed would lock the
+// register at zero, so it's replaced with 0xACE1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LfsrRng {
+    state: u16,
+}
+
+impl LfsrRng {
+    pub fn new(seed: u16) -> Self {
+        let state = if seed == 0 { 0xACE1 } else { seed };
+        return Self { state };
+    }
+
+    // Clocks the register 8 times, so each output byte is all new bits
+    pub fn next_byte(&mut self) -> u8 {
+        for _ in 0..8 {
+            let lsb = self.state & 1;
+            self.state >>= 1;
+            if lsb != 0 {
+                self.state ^= 0xB400;
+            }
+        }
+        return (self.state & 0xFF) as u8;
+    }
+}
+
+// Where CXNN gets its random bytes from. With rewind on this is cloned
+// every frame, so StdRng stays inline rather than paying for a Box each time.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+pub(crate) enum Random {
+    Std(StdRng),
+    Lfsr(LfsrRng),
+}
+
+impl Random {
+    pub(crate) fn next_byte(&mut self) -> u8 {
+        match self {
+            Random::Std(rng) => return rng.gen(),
+            Random::Lfsr(rng) => return rng.next_byte(),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+// serde only derives for arrays up to 32 elements, so RAM and the screen
+// go through these with #[serde(with = "serde_arrays")]
+use serde::de::{Error, SeqAccess, Visitor};
+use serde::ser::SerializeTuple;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use core::fmt;
+use core::marker::PhantomData;
+
+pub fn serialize<S, T, const N: usize>(arr: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize,
+{
+    let mut tup = serializer.serialize_tuple(N)?;
+    for item in arr {
+        tup.serialize_element(item)?;
+    }
+    return tup.end();
+}
+
+pub fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Copy + Default,
+{
+    return deserializer.deserialize_tuple(N, ArrayVisitor::<T, N>(PhantomData));
+}
+
+struct ArrayVisitor<T, const N: usize>(PhantomData<T>);
+
+impl<'de, T, const N: usize> Visitor<'de> for ArrayVisitor<T, N>
+where
+    T: Deserialize<'de> + Copy + Default,
+{
+    type Value = [T; N];
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an array of length {}", N)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<[T; N], A::Error> {
+        let mut arr = [T::default(); N];
+        for (i, slot) in arr.iter_mut().enumerate() {
+            *slot = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(i, &self))?;
+        }
+        return Ok(arr);
+    }
+}
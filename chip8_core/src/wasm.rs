@@ -0,0 +1,62 @@
+use crate::Emulator;
+use alloc::vec::Vec;
+use wasm_bindgen::prelude::*;
+
+// An Emulator with an interface that crosses the JS boundary cleanly:
+// owned byte buffers rather than borrowed bool slices.
+#[wasm_bindgen]
+pub struct WasmEmulator {
+    chip8: Emulator,
+}
+
+#[wasm_bindgen]
+impl WasmEmulator {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmEmulator {
+        return WasmEmulator { chip8: Emulator::new() };
+    }
+
+    pub fn tick(&mut self) {
+        self.chip8.tick();
+    }
+
+    pub fn tick_timers(&mut self) {
+        self.chip8.tick_timers();
+    }
+
+    pub fn run_frame(&mut self) {
+        self.chip8.run_frame();
+    }
+
+    pub fn reset(&mut self) {
+        self.chip8.reset();
+    }
+
+    pub fn keypress(&mut self, idx: usize, pressed: bool) {
+        self.chip8.keypress(idx, pressed);
+    }
+
+    pub fn load(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        return self.chip8.load(data)
+            .map_err(|e| JsValue::from_str(&e.to_string()));
+    }
+
+    // Packed as in Emulator::get_display_packed
+    pub fn display_bytes(&self) -> Vec<u8> {
+        return self.chip8.get_display_packed();
+    }
+
+    pub fn display_width(&self) -> usize {
+        return self.chip8.display_dimensions().0;
+    }
+
+    pub fn display_height(&self) -> usize {
+        return self.chip8.display_dimensions().1;
+    }
+}
+
+impl Default for WasmEmulator {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
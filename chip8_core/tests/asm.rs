@@ -0,0 +1,66 @@
+use chip8_core::*;
+
+#[test]
+fn disassembles_each_opcode_family() {
+    assert_eq!(disassemble(0x00E0), "CLS");
+    assert_eq!(disassemble(0x00EE), "RET");
+    assert_eq!(disassemble(0x1234), "JP 0x234");
+    assert_eq!(disassemble(0x2ABC), "CALL 0xABC");
+    assert_eq!(disassemble(0x6A02), "LD V10, 0x02");
+    assert_eq!(disassemble(0x8124), "ADD V1, V2");
+    assert_eq!(disassemble(0x8127), "SUBN V1, V2");
+    assert_eq!(disassemble(0xA123), "LD I, 0x123");
+    assert_eq!(disassemble(0xD015), "DRW V0, V1, 5");
+    assert_eq!(disassemble(0xF033), "LD B, V0");
+    assert_eq!(disassemble(0xF10A), "LD V1, K");
+}
+
+#[test]
+fn unknown_opcodes_disassemble_as_data() {
+    assert_eq!(disassemble(0x5121), "DW 0x5121");
+    assert_eq!(disassemble(0xFFFF), "DW 0xFFFF");
+}
+
+#[test]
+fn decodes_opcodes_into_instructions() {
+    assert_eq!(decode(0x0000), Instruction::Nop);
+    assert_eq!(decode(0x00E0), Instruction::Cls);
+    assert_eq!(decode(0x1234), Instruction::Jump { nnn: 0x234 });
+    assert_eq!(decode(0x3A42), Instruction::SkipEqImm { x: 0xA, nn: 0x42 });
+    assert_eq!(decode(0x8125), Instruction::Sub { x: 1, y: 2 });
+    assert_eq!(decode(0xB123), Instruction::JumpOffset { x: 1, nnn: 0x123 });
+    assert_eq!(decode(0xD01F), Instruction::Draw { x: 0, y: 1, n: 0xF });
+    assert_eq!(decode(0xF565), Instruction::Load { x: 5 });
+    assert_eq!(decode(0xF000), Instruction::LoadLongI);
+    assert_eq!(decode(0x5121), Instruction::Unknown(0x5121));
+}
+
+#[test]
+fn assembles_a_small_program() {
+    let source = "
+        LD VA, 0x02   ; counter
+        JP 0x200
+        DRW V0, V1, 5
+        ld i, 768
+    ";
+    assert_eq!(assemble(source), Ok(vec![0x6A, 0x02, 0x12, 0x00, 0xD0, 0x15, 0xA3, 0x00]));
+}
+
+#[test]
+fn assembled_instructions_disassemble_to_the_same_text() {
+    let lines = [
+        "CLS", "CALL 0x2AB", "SE V3, 0x10", "ADD V1, V2", "SHL V4, V5", "LD I, 0x123",
+        "RND V7, 0x0F", "DRW V0, V1, 5", "SKNP V2", "LD V1, K", "LD B, V0", "LD [I], V15", "SCD 3",
+    ];
+    for line in lines {
+        let bytes = assemble(line).unwrap();
+        assert_eq!(disassemble(u16::from_be_bytes([bytes[0], bytes[1]])), line);
+    }
+}
+
+#[test]
+fn assembler_errors_name_the_line() {
+    assert_eq!(assemble("CLS\nFOO V0"), Err(AsmError::UnknownMnemonic { line: 2 }));
+    assert_eq!(assemble("JP V1"), Err(AsmError::BadOperands { line: 1 }));
+    assert_eq!(assemble("\nLD V0, 0x100"), Err(AsmError::OutOfRange { line: 2 }));
+}
@@ -0,0 +1,10 @@
+#![allow(clippy::needless_return)]
+
+use chip8_core::Emulator;
+
+// A fresh emulator with `rom` loaded at the start address
+pub fn load(rom: &[u8]) -> Emulator {
+    let mut emulator = Emulator::new();
+    emulator.load(rom).unwrap();
+    return emulator;
+}
@@ -0,0 +1,150 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use chip8_core::*;
+use common::load;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn emulator_can_move_to_another_thread() {
+    fn assert_send<T: Send>() {}
+    assert_send::<Emulator>();
+}
+
+#[test]
+fn step_reports_each_instruction() {
+    // LD VA, 0x02; JP 0x200
+    let mut emulator = load(&[0x6A, 0x02, 0x12, 0x00]);
+    assert_eq!(emulator.step(), Ok(ExecutedInstruction { pc: 0x200, opcode: 0x6A02, operand: None }));
+    assert_eq!(emulator.step(), Ok(ExecutedInstruction { pc: 0x202, opcode: 0x1200, operand: None }));
+    assert_eq!(emulator.get_pc(), 0x200);
+}
+
+#[test]
+fn breakpoint_stops_before_its_instruction() {
+    // V0 := 1; V1 := 2; V2 := 3; JP 0x206
+    let mut emulator = load(&[0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x12, 0x06]);
+    emulator.add_breakpoint(0x204);
+    emulator.tick_checked().unwrap();
+    emulator.tick_checked().unwrap();
+    assert_eq!(emulator.tick_checked(), Err(ExecError::Breakpoint(0x204)));
+    assert_eq!(emulator.get_pc(), 0x204);
+    assert_eq!((emulator.get_v_reg(0), emulator.get_v_reg(1), emulator.get_v_reg(2)), (1, 2, 0));
+
+    // Carrying on runs the instruction it stopped at
+    assert_eq!(emulator.step(), Ok(ExecutedInstruction { pc: 0x204, opcode: 0x6203, operand: None }));
+    assert_eq!(emulator.get_v_reg(2), 3);
+}
+
+#[test]
+fn step_and_trace_report_all_of_f000() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    // LD I, LONG 0x1234
+    let mut emulator = load(&[0xF0, 0x00, 0x12, 0x34]);
+    emulator.set_trace_hook(Box::new(move |executed| log.lock().unwrap().push(executed)));
+    let executed = ExecutedInstruction { pc: 0x200, opcode: 0xF000, operand: Some(0x1234) };
+    assert_eq!(emulator.step(), Ok(executed));
+    assert_eq!(*seen.lock().unwrap(), [executed]);
+}
+
+#[test]
+fn run_frame_runs_through_breakpoints() {
+    // loop: V0 += 1; JP loop
+    let mut emulator = load(&[0x70, 0x01, 0x12, 0x00]);
+    emulator.set_clock_hz(660);
+    emulator.add_breakpoint(0x200);
+    emulator.run_frame();
+    // 11 instructions, so round the loop 6 times
+    assert_eq!(emulator.get_v_reg(0), 6);
+}
+
+#[test]
+fn trace_hook_sees_every_instruction() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    // V0 := 1; V0 += 2; JP 0x200
+    let mut emulator = load(&[0x60, 0x01, 0x70, 0x02, 0x12, 0x00]);
+    emulator.set_trace_hook(Box::new(move |executed: ExecutedInstruction| {
+        log.lock().unwrap().push((executed.opcode, executed.pc));
+    }));
+    for _ in 0..4 {
+        emulator.tick();
+    }
+    assert_eq!(*seen.lock().unwrap(), [(0x6001, 0x200), (0x7002, 0x202), (0x1200, 0x204), (0x6001, 0x200)]);
+
+    emulator.clear_trace_hook();
+    emulator.tick();
+    assert_eq!(seen.lock().unwrap().len(), 4);
+}
+
+#[test]
+fn histogram_counts_each_opcode_family() {
+    // V0 := 1; V0 += 2; I := 0; V0 += V1; V1 := 5; JP 0x20A
+    let mut emulator = load(&[0x60, 0x01, 0x70, 0x02, 0xA0, 0x00, 0x80, 0x14, 0x61, 0x05, 0x12, 0x0A]);
+    for _ in 0..8 {
+        emulator.tick();
+    }
+    let mut expected = [0; 16];
+    expected[0x1] = 3;
+    expected[0x6] = 2;
+    expected[0x7] = 1;
+    expected[0x8] = 1;
+    expected[0xA] = 1;
+    assert_eq!(emulator.opcode_histogram(), expected);
+
+    emulator.reset_opcode_histogram();
+    assert_eq!(emulator.opcode_histogram(), [0; 16]);
+}
+
+#[test]
+fn smc_hook_fires_on_writes_over_run_code() {
+    let hits = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&hits);
+    // I := 0x202; LD [I], V2, overwriting itself and the word after it
+    let mut emulator = load(&[0xA2, 0x02, 0xF2, 0x55]);
+    emulator.set_smc_hook(Box::new(move |addr| log.lock().unwrap().push(addr)));
+    emulator.tick();
+    emulator.tick();
+    assert_eq!(*hits.lock().unwrap(), [0x202, 0x203]);
+}
+
+#[test]
+fn smc_hook_covers_all_of_f000() {
+    let hits = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&hits);
+    // LD I, LONG 0x200; LD [I], V3, overwriting all four bytes of the F000
+    let mut emulator = load(&[0xF0, 0x00, 0x02, 0x00, 0xF3, 0x55]);
+    emulator.set_smc_hook(Box::new(move |addr| log.lock().unwrap().push(addr)));
+    emulator.tick();
+    emulator.tick();
+    assert_eq!(*hits.lock().unwrap(), [0x200, 0x201, 0x202, 0x203]);
+}
+
+#[test]
+fn stack_frames_list_return_addresses() {
+    // CALL 0x204; JP 0x202; CALL 0x208; RET; RET
+    let mut emulator = load(&[0x22, 0x04, 0x12, 0x02, 0x22, 0x08, 0x00, 0xEE, 0x00, 0xEE]);
+    emulator.tick();
+    emulator.tick();
+    assert_eq!(emulator.stack_frames(), &[0x202, 0x206]);
+    assert_eq!(emulator.stack_depth(), 2);
+
+    emulator.tick();
+    assert_eq!(emulator.stack_frames(), &[0x202]);
+}
+
+#[test]
+fn scan_flags_illegal_opcodes() {
+    // V0 := 1; an illegal 5XY1; JP 0x204
+    let emulator = load(&[0x60, 0x01, 0x51, 0x21, 0x12, 0x04]);
+    assert_eq!(emulator.scan_opcodes(), [(0x202, 0x5121)]);
+}
+
+#[test]
+fn scan_skips_the_address_after_f000() {
+    // LD I, LONG 0x5121; JP 0x204
+    let emulator = load(&[0xF0, 0x00, 0x51, 0x21, 0x12, 0x04]);
+    assert_eq!(emulator.scan_opcodes(), []);
+}
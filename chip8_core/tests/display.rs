@@ -0,0 +1,539 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use chip8_core::*;
+use common::load;
+
+fn pixel(emulator: &Emulator, x: usize, y: usize) -> bool {
+    let (width, _) = emulator.display_dimensions();
+    return emulator.get_display()[y * width + x];
+}
+
+fn lit_count(emulator: &Emulator) -> usize {
+    return emulator.get_display().iter().filter(|&&pixel| pixel).count();
+}
+
+#[test]
+fn draws_in_both_resolutions() {
+    // HIGH; V0 := 100; V1 := 40; I := font 0; DRW V0, V1, 5; LOW; DRW V0, V1, 5
+    let mut emulator = load(&[0x00, 0xFF, 0x60, 100, 0x61, 40, 0xA0, 0x00, 0xD0, 0x15, 0x00, 0xFE, 0xD0, 0x15]);
+    emulator.tick();
+    assert!(emulator.is_hi_res());
+    assert_eq!(emulator.display_dimensions(), (128, 64));
+    assert_eq!(emulator.get_display().len(), 128 * 64);
+
+    for _ in 0..4 {
+        emulator.tick();
+    }
+    assert!(pixel(&emulator, 100, 40));
+    assert_eq!(lit_count(&emulator), 14);
+
+    // Switching back clears the screen, and coordinates wrap to the smaller one
+    emulator.tick();
+    assert_eq!(emulator.display_dimensions(), (64, 32));
+    assert_eq!(lit_count(&emulator), 0);
+    emulator.tick();
+    assert!(pixel(&emulator, 100 % 64, 40 % 32));
+    assert_eq!(lit_count(&emulator), 14);
+}
+
+// (x, y) of each lit pixel, row by row
+fn lit(emulator: &Emulator) -> Vec<(usize, usize)> {
+    let (width, _) = emulator.display_dimensions();
+    return emulator.get_display().iter().enumerate()
+        .filter(|(_, &pixel)| pixel)
+        .map(|(idx, _)| (idx % width, idx / width))
+        .collect();
+}
+
+#[test]
+fn scrolls_move_pixels_and_clear_edges() {
+    // I := 0x20E; V0 := 56; V1 := 0; DRW V0, V1, 1; SCR 4; SCD 2; SCL 4
+    let mut emulator = load(&[
+        0xA2, 0x0E, 0x60, 56, 0x61, 0, 0xD0, 0x11, 0x00, 0xFB, 0x00, 0xC2, 0x00, 0xFC,
+        0b1000_0001,
+    ]);
+    for _ in 0..4 {
+        emulator.tick();
+    }
+    assert_eq!(lit(&emulator), [(56, 0), (63, 0)]);
+
+    // The pixel pushed off the right edge is gone, not wrapped round
+    emulator.tick();
+    assert_eq!(lit(&emulator), [(60, 0)]);
+    emulator.tick();
+    assert_eq!(lit(&emulator), [(60, 2)]);
+    emulator.tick();
+    assert_eq!(lit(&emulator), [(56, 2)]);
+}
+
+#[test]
+fn scrolls_use_hi_res_pixels_in_hi_res() {
+    // HIGH; I := 0x20E; V0 := 0; DRW V0, V0, 1; SCD 3; SCL 4
+    let mut emulator = load(&[
+        0x00, 0xFF, 0xA2, 0x0E, 0x60, 0, 0xD0, 0x01, 0x00, 0xC3, 0x00, 0xFC, 0x00, 0x00,
+        0b0000_1001,
+    ]);
+    for _ in 0..4 {
+        emulator.tick();
+    }
+    assert_eq!(lit(&emulator), [(4, 0), (7, 0)]);
+    emulator.tick();
+    assert_eq!(lit(&emulator), [(4, 3), (7, 3)]);
+
+    // Nothing wraps round onto the right edge
+    emulator.tick();
+    assert_eq!(lit(&emulator), [(0, 3), (3, 3)]);
+}
+
+#[test]
+fn dxy0_draws_a_16x16_sprite() {
+    // HIGH; I := 0x20A; V0 := 8; DRW V0, V0, 0; then 32 bytes of sprite
+    let mut rom = vec![0x00, 0xFF, 0xA2, 0x0A, 0x60, 8, 0xD0, 0x00, 0x00, 0x00];
+    rom.extend_from_slice(&[0xFF; 32]);
+    let mut emulator = load(&rom);
+    for _ in 0..4 {
+        emulator.tick();
+    }
+    assert_eq!(lit_count(&emulator), 256);
+    assert!(pixel(&emulator, 8, 8) && pixel(&emulator, 23, 23));
+    assert!(!pixel(&emulator, 24, 23) && !pixel(&emulator, 23, 24));
+}
+
+#[test]
+fn packed_display_has_a_bit_per_pixel() {
+    // A single pixel at (0, 0), (9, 0) and (63, 31)
+    let mut emulator = load(&[
+        0x60, 0, 0x61, 0, 0xA2, 0x12, 0xD0, 0x11, 0x60, 9, 0xD0, 0x11, 0x60, 63, 0x61, 31, 0xD0, 0x11,
+        0x80,
+    ]);
+    for _ in 0..9 {
+        emulator.tick();
+    }
+    let packed = emulator.get_display_packed();
+    assert_eq!(packed.len(), 64 * 32 / 8);
+    assert_eq!(packed[0], 0x80);
+    assert_eq!(packed[1], 0x40);
+    assert_eq!(packed[255], 0x01);
+    assert_eq!(packed.iter().map(|byte| byte.count_ones()).sum::<u32>(), 3);
+}
+
+#[test]
+fn draw_marks_only_its_rows_dirty() {
+    // V0 := 10; I := font 0; DRW V0, V0, 5
+    let mut emulator = load(&[0x60, 10, 0xA0, 0x00, 0xD0, 0x05]);
+    emulator.take_dirty_rows();
+    for _ in 0..3 {
+        emulator.tick();
+    }
+    assert_eq!(emulator.take_dirty_rows(), 0b11111 << 10);
+    assert_eq!(emulator.take_dirty_rows(), 0);
+}
+
+#[test]
+fn second_plane_draws_leave_the_first_alone() {
+    // PLANE 2; I := font 0; DRW V0, V0, 5
+    let mut emulator = load(&[0xF2, 0x01, 0xA0, 0x00, 0xD0, 0x05]);
+    for _ in 0..3 {
+        emulator.tick();
+    }
+    assert!(emulator.get_plane(0).iter().all(|&pixel| !pixel));
+    assert_eq!(emulator.get_plane(1).iter().filter(|&&pixel| pixel).count(), 14);
+    assert_eq!(emulator.get_display_planes()[0], 0b10);
+}
+
+#[test]
+fn both_planes_take_consecutive_sprites() {
+    // PLANE 3; I := 0x208; DRW V0, V0, 1; then plane 0's row and plane 1's
+    let mut emulator = load(&[0xF3, 0x01, 0xA2, 0x08, 0xD0, 0x01, 0x00, 0x00, 0x80, 0x40]);
+    for _ in 0..3 {
+        emulator.tick();
+    }
+    assert_eq!(&emulator.get_display_planes()[..2], &[0b01, 0b10]);
+}
+
+// Draws a row of 8 pixels at (62, 0) and returns what lit up
+fn draw_at_right_edge(sprite_wrapping: bool) -> Vec<(usize, usize)> {
+    // V0 := 62; V1 := 0; I := 0x20A; DRW V0, V1, 1; then the sprite
+    let quirks = QuirkConfig { sprite_wrapping, ..QuirkConfig::default() };
+    let mut emulator = Emulator::with_quirks(quirks);
+    emulator.load(&[0x60, 62, 0x61, 0, 0xA2, 0x0A, 0xD0, 0x11, 0x00, 0x00, 0xFF]).unwrap();
+    for _ in 0..4 {
+        emulator.tick();
+    }
+    return lit(&emulator);
+}
+
+#[test]
+fn sprites_clip_at_the_edge_by_default() {
+    assert_eq!(draw_at_right_edge(false), [(62, 0), (63, 0)]);
+}
+
+#[test]
+fn sprites_wrap_with_quirk() {
+    assert_eq!(draw_at_right_edge(true), [(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0), (62, 0), (63, 0)]);
+}
+
+#[test]
+fn custom_font_glyphs_are_drawn() {
+    let mut emulator = Emulator::new();
+    emulator.set_fontset(&[0x80, 0x40, 0x20, 0x10, 0x08]).unwrap();
+    // LD F, V0; DRW V0, V0, 5
+    emulator.load(&[0xF0, 0x29, 0xD0, 0x05]).unwrap();
+    emulator.tick();
+    emulator.tick();
+    assert_eq!(lit(&emulator), [(0, 0), (1, 1), (2, 2), (3, 3), (4, 4)]);
+}
+
+#[test]
+fn too_large_font_is_rejected() {
+    let mut emulator = Emulator::new();
+    assert_eq!(emulator.set_fontset(&[0; 81]), Err(LoadError::TooLarge { size: 81, max: 80 }));
+}
+
+#[test]
+fn clear_screen_blanks_every_pixel() {
+    // I := font 0; DRW V0, V0, 5
+    let mut emulator = load(&[0xA0, 0x00, 0xD0, 0x05]);
+    emulator.tick();
+    emulator.tick();
+    assert!(lit_count(&emulator) > 0);
+    emulator.clear_screen();
+    assert_eq!(lit_count(&emulator), 0);
+}
+
+#[test]
+fn draw_flag_is_set_by_draws_only() {
+    // V0 := 1; DRW V0, V0, 5
+    let mut emulator = load(&[0x60, 0x01, 0xD0, 0x05]);
+    emulator.tick();
+    assert!(!emulator.take_draw_flag());
+    emulator.tick();
+    assert!(emulator.take_draw_flag());
+    assert!(!emulator.take_draw_flag());
+}
+
+#[test]
+fn collision_count_is_the_pixels_erased() {
+    // I := 0x20C; DRW V0, V0, 2; I := 0x20E; DRW V0, V0, 2; then the sprites
+    let mut emulator = load(&[
+        0xA2, 0x0C, 0xD0, 0x02, 0xA2, 0x0E, 0xD0, 0x02, 0x00, 0x00, 0x00, 0x00,
+        0xFF, 0x81, 0x0F, 0x01,
+    ]);
+    emulator.tick();
+    emulator.tick();
+    assert_eq!(emulator.last_collision_count(), 0);
+    assert_eq!(emulator.get_v_reg(0xF), 0);
+
+    emulator.tick();
+    emulator.tick();
+    assert_eq!(emulator.last_collision_count(), 5);
+    assert_eq!(emulator.get_v_reg(0xF), 1);
+}
+
+const FG: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const BG: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+
+// The RGBA pixel at (x, y) of an image `width` pixels wide
+fn rgba_at(image: &[u8], width: usize, x: usize, y: usize) -> [u8; 4] {
+    let idx = 4 * (x + width * y);
+    return image[idx..idx + 4].try_into().unwrap();
+}
+
+#[test]
+fn render_rgba_scales_each_pixel_up() {
+    // HIGH; V0 := 5; V1 := 3; I := 0x20A; DRW V0, V1, 1
+    let mut emulator = load(&[0x00, 0xFF, 0x60, 5, 0x61, 3, 0xA2, 0x0A, 0xD0, 0x11, 0x80]);
+    for _ in 0..5 {
+        emulator.tick();
+    }
+
+    let mut image = Vec::new();
+    emulator.render_rgba(2, FG, BG, &mut image);
+    assert_eq!(image.len(), 256 * 128 * 4);
+    for y in 0..128 {
+        for x in 0..256 {
+            let lit = (10..12).contains(&x) && (6..8).contains(&y);
+            assert_eq!(rgba_at(&image, 256, x, y), if lit { FG } else { BG }, "({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn rows_iterate_the_display_by_row() {
+    // V0 := 10; V1 := 5; I := 0x208; DRW V0, V1, 1
+    let mut emulator = load(&[0x60, 10, 0x61, 5, 0xA2, 0x08, 0xD0, 0x11, 0x80]);
+    for _ in 0..4 {
+        emulator.tick();
+    }
+    let rows: Vec<_> = emulator.rows().collect();
+    assert_eq!(rows.len(), 32);
+    assert!(rows.iter().all(|row| row.len() == 64));
+    assert!(rows[5][10]);
+    assert_eq!(rows[5].get(10), Some(true));
+    assert_eq!(rows[5].get(64), None);
+    assert_eq!(rows[5].iter().filter(|&pixel| pixel).count(), 1);
+    assert!(!rows[4][10] && !rows[5][9]);
+}
+
+#[test]
+fn scroll_up_moves_rows_and_clears_the_bottom() {
+    // V0 := 4; V1 := 29; I := 0x20C; DRW V0, V1, 3; SCU 3; then the sprite
+    let mut emulator = load(&[0x60, 4, 0x61, 29, 0xA2, 0x0C, 0xD0, 0x13, 0x00, 0xD3, 0x00, 0x00, 0x80, 0x40, 0x20]);
+    for _ in 0..4 {
+        emulator.tick();
+    }
+    assert_eq!(lit(&emulator), [(4, 29), (5, 30), (6, 31)]);
+    emulator.tick();
+    assert_eq!(lit(&emulator), [(4, 26), (5, 27), (6, 28)]);
+    assert!(emulator.rows().skip(29).all(|row| row.iter().all(|pixel| !pixel)));
+}
+
+#[test]
+fn packing_puts_pixels_in_the_chosen_bit_order() {
+    // V0 := 2; I := 0x206; DRW V0, V1, 1; then the sprite
+    let mut emulator = load(&[0x60, 2, 0xA2, 0x06, 0xD0, 0x11, 0x80]);
+    for _ in 0..3 {
+        emulator.tick();
+    }
+    let mut buf = [0; 256];
+    emulator.pack_display_into(&mut buf, BitOrder::MsbFirst);
+    assert_eq!(buf[0], 0b0010_0000);
+    emulator.pack_display_into(&mut buf, BitOrder::LsbFirst);
+    assert_eq!(buf[0], 0b0000_0100);
+    assert!(buf[1..].iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn draw_bounds_cover_the_changed_pixels() {
+    // V0 := 10; I := 0x208; DRW V0, V0, 5; then the sprite
+    let mut emulator = load(&[0x60, 10, 0xA2, 0x08, 0xD0, 0x05, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    for _ in 0..3 {
+        emulator.tick();
+    }
+    assert_eq!(emulator.last_draw_bounds(), Some((10, 10, 17, 14)));
+}
+
+#[test]
+fn draw_that_changes_nothing_has_no_bounds() {
+    // I := 0x206; DRW V0, V0, 1; then a blank row
+    let mut emulator = load(&[0xA2, 0x06, 0xD0, 0x01, 0x00, 0x00, 0x00]);
+    emulator.tick();
+    emulator.tick();
+    assert_eq!(emulator.last_draw_bounds(), None);
+}
+
+#[test]
+fn cls_clears_only_the_selected_planes() {
+    // I := font 0; DRW V0, V0, 5; PLANE 2; CLS
+    let mut emulator = load(&[0xA0, 0x00, 0xD0, 0x05, 0xF2, 0x01, 0x00, 0xE0]);
+    for _ in 0..4 {
+        emulator.tick();
+    }
+    assert_eq!(lit_count(&emulator), 14);
+}
+
+#[test]
+fn lit_pixels_lists_each_lit_pixel() {
+    // I := 0x214; then a one pixel DRW at (40, 20), (3, 1) and (63, 0)
+    let mut emulator = load(&[
+        0xA2, 0x14, 0x60, 40, 0x61, 20, 0xD0, 0x11, 0x60, 3, 0x61, 1, 0xD0, 0x11,
+        0x60, 63, 0x61, 0, 0xD0, 0x11, 0x80,
+    ]);
+    for _ in 0..10 {
+        emulator.tick();
+    }
+    let pixels: Vec<_> = emulator.lit_pixels().collect();
+    assert_eq!(pixels, [(63, 0), (3, 1), (40, 20)]);
+}
+
+#[test]
+fn draw_sprite_draws_from_a_host_buffer() {
+    // The font's 1
+    let one = [0x20, 0x60, 0x20, 0x20, 0x70];
+    let mut emulator = Emulator::new();
+    assert!(!emulator.draw_sprite(1, 2, &one));
+    assert_eq!(lit(&emulator), [(3, 2), (2, 3), (3, 3), (3, 4), (3, 5), (2, 6), (3, 6), (4, 6)]);
+
+    // Drawing it again erases it, and reports the collision without touching VF
+    assert!(emulator.draw_sprite(1, 2, &one));
+    assert_eq!(lit_count(&emulator), 0);
+    assert_eq!(emulator.get_v_reg(0xF), 0);
+}
+
+// How far right and down the lit block at the top left of `image` reaches
+fn top_left_block(image: &[u8], width: usize) -> (usize, usize) {
+    let across = (0..width).take_while(|&x| rgba_at(image, width, x, 0) == FG).count();
+    let down = (0..image.len() / 4 / width).take_while(|&y| rgba_at(image, width, 0, y) == FG).count();
+    return (across, down);
+}
+
+#[test]
+fn render_rgba_fills_the_same_size_in_both_modes() {
+    // LOW, then HIGH
+    let mut emulator = load(&[0x00, 0xFE, 0x00, 0xFF]);
+    let (mut lo_res, mut hi_res) = (Vec::new(), Vec::new());
+
+    emulator.tick();
+    emulator.draw_sprite(0, 0, &[0x80]);
+    emulator.render_rgba(3, FG, BG, &mut lo_res);
+    emulator.tick();
+    emulator.draw_sprite(0, 0, &[0x80]);
+    emulator.render_rgba(3, FG, BG, &mut hi_res);
+
+    assert_eq!(lo_res.len(), hi_res.len());
+    assert_eq!(top_left_block(&lo_res, 128 * 3), (6, 6));
+    assert_eq!(top_left_block(&hi_res, 128 * 3), (3, 3));
+}
+
+#[test]
+fn sprite_height_of_dxy0_depends_on_the_mode() {
+    let classic = QuirkConfig { lores_big_sprites: false, ..QuirkConfig::default() };
+    let mut emulator = Emulator::with_quirks(classic);
+    emulator.load(&[0x00, 0xFF]).unwrap();
+    assert_eq!(emulator.sprite_height(0xD120), 0);
+    assert_eq!(emulator.sprite_height(0xD125), 5);
+    emulator.tick();
+    assert_eq!(emulator.sprite_height(0xD120), 16);
+
+    let emulator = Emulator::new();
+    assert_eq!(emulator.sprite_height(0xD120), 16);
+    assert_eq!(emulator.sprite_height(0x6120), 0);
+}
+
+#[test]
+fn display_to_string_draws_with_blocks() {
+    // I := font 0; DRW V0, V0, 5
+    let mut emulator = load(&[0xA0, 0x00, 0xD0, 0x05]);
+    emulator.tick();
+    emulator.tick();
+    let text = emulator.display_to_string();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 32);
+    assert!(lines.iter().all(|line| line.chars().count() == 64));
+    assert!(lines[0].starts_with("████ "));
+    for line in &lines[1..4] {
+        assert!(line.starts_with("█  █ "));
+    }
+    assert!(lines[4].starts_with("████ "));
+    assert!(lines[5].trim().is_empty());
+}
+
+// XORs an 8-wide sprite onto a plain bool screen the way DXYN does with
+// clipping, returning whether anything was erased
+fn model_draw(model: &mut [bool], width: usize, height: usize, x: usize, y: usize, sprite: &[u8]) -> bool {
+    let (x, y) = (x % width, y % height);
+    let mut collided = false;
+    for (row, &bits) in sprite.iter().enumerate() {
+        for col in 0..8 {
+            if bits & (0x80 >> col) == 0 || x + col >= width || y + row >= height {
+                continue;
+            }
+            let idx = x + col + width * (y + row);
+            collided |= model[idx];
+            model[idx] ^= true;
+        }
+    }
+    return collided;
+}
+
+#[test]
+fn screen_matches_a_plain_bool_model() {
+    for hi_res in [false, true] {
+        // HIGH or LOW
+        let mut emulator = load(&[0x00, if hi_res { 0xFF } else { 0xFE }]);
+        emulator.tick();
+        let (width, height) = emulator.display_dimensions();
+        let mut model = vec![false; width * height];
+
+        // A spread of sprites, overlapping each other and the edges
+        let mut seed = 12345u32;
+        for _ in 0..200 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let (x, y) = ((seed >> 8) as u8, (seed >> 16) as u8);
+            let sprite = [(seed >> 3) as u8, (seed >> 11) as u8, (seed >> 19) as u8];
+            let collided = model_draw(&mut model, width, height, x as usize, y as usize, &sprite);
+            assert_eq!(emulator.draw_sprite(x, y, &sprite), collided);
+        }
+
+        assert_eq!(emulator.get_display(), model);
+        for (y, row) in emulator.rows().enumerate() {
+            assert_eq!(row.iter().collect::<Vec<_>>(), model[y * width..(y + 1) * width]);
+        }
+        for (idx, &pixel) in model.iter().enumerate() {
+            assert_eq!(emulator.pixel(idx % width, idx / width), pixel);
+        }
+        assert!(!emulator.pixel(width, 0) && !emulator.pixel(0, height));
+    }
+}
+
+#[test]
+fn draw_reads_the_sprite_at_i() {
+    // DRW V0, V0, 2
+    let mut emulator = load(&[0xD0, 0x02]);
+    emulator.load_at(0x400, &[0xC0, 0x01]).unwrap();
+    emulator.set_i(0x400);
+    assert_eq!(emulator.get_i(), 0x400);
+    emulator.tick();
+    assert_eq!(lit(&emulator), [(0, 0), (1, 0), (7, 1)]);
+}
+
+#[test]
+fn set_i_wraps_into_ram() {
+    let mut emulator = Emulator::new();
+    emulator.set_i(0x1400);
+    assert_eq!(emulator.get_i(), 0x400);
+}
+
+// Draws 3 rows of 8 pixels in hi-res at y = 0, then 1, then 62, giving VF
+// after each draw
+fn hi_res_vf(schip_collision_count: bool) -> [u8; 3] {
+    // HIGH; I := 0x214; V1 := 1; V2 := 62; DRW V0, V0, 3; DRW V0, V1, 3; DRW V0, V2, 3
+    let quirks = QuirkConfig { schip_collision_count, ..QuirkConfig::default() };
+    let mut emulator = Emulator::with_quirks(quirks);
+    emulator.load(&[
+        0x00, 0xFF, 0xA2, 0x14, 0x61, 1, 0x62, 62, 0xD0, 0x03, 0xD0, 0x13, 0xD0, 0x23,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF,
+    ]).unwrap();
+    for _ in 0..4 {
+        emulator.tick();
+    }
+    let mut vf = [0; 3];
+    for flag in vf.iter_mut() {
+        emulator.tick();
+        *flag = emulator.get_v_reg(0xF);
+    }
+    return vf;
+}
+
+#[test]
+fn schip_counts_collided_and_clipped_rows() {
+    assert_eq!(hi_res_vf(true), [0, 2, 1]);
+}
+
+#[test]
+fn vf_is_a_flag_without_the_schip_count() {
+    assert_eq!(hi_res_vf(false), [0, 1, 0]);
+}
+
+#[test]
+fn display_hash_tells_screens_apart() {
+    let mut first = Emulator::new();
+    let mut second = Emulator::new();
+    first.draw_sprite(10, 10, &[0xAA, 0x55]);
+    second.draw_sprite(10, 10, &[0xAA, 0x55]);
+    assert_eq!(first.display_hash(), second.display_hash());
+
+    second.draw_sprite(63, 31, &[0x80]);
+    assert_ne!(first.display_hash(), second.display_hash());
+}
+
+#[test]
+fn blank_screens_hash_by_resolution() {
+    // HIGH
+    let mut emulator = load(&[0x00, 0xFF]);
+    let lo_res = emulator.display_hash();
+    emulator.tick();
+    assert_ne!(emulator.display_hash(), lo_res);
+}
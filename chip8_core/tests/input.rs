@@ -0,0 +1,137 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use chip8_core::*;
+use common::load;
+
+#[test]
+fn keys_convert_to_their_index() {
+    assert_eq!(usize::from(Key::Key0), 0);
+    assert_eq!(usize::from(Key::KeyA), 0xA);
+    assert_eq!(usize::from(Key::KeyF), 0xF);
+
+    // V0 := 0xC; SKP V0
+    let mut emulator = load(&[0x60, 0x0C, 0xE0, 0x9E]);
+    emulator.keypress_key(Key::KeyC, true);
+    emulator.tick();
+    emulator.tick();
+    assert_eq!(emulator.get_pc(), 0x206);
+}
+
+#[test]
+fn out_of_range_keys_are_ignored() {
+    // SKNP V0, with V0 = 0
+    let mut emulator = load(&[0xE0, 0xA1]);
+    emulator.keypress(16, true);
+    emulator.keypress(usize::MAX, true);
+    emulator.tick();
+    assert_eq!(emulator.get_pc(), 0x204);
+}
+
+#[test]
+fn key_wait_finishes_on_release() {
+    // LD V0, K; V1 := 1
+    let mut emulator = load(&[0xF0, 0x0A, 0x61, 0x01]);
+    emulator.tick();
+    assert_eq!(emulator.get_pc(), 0x200);
+
+    // Held down over several ticks, the wait goes on
+    emulator.keypress(7, true);
+    for _ in 0..3 {
+        emulator.tick();
+        assert_eq!(emulator.get_pc(), 0x200);
+    }
+
+    emulator.keypress(7, false);
+    emulator.tick();
+    assert_eq!(emulator.get_pc(), 0x202);
+    assert_eq!(emulator.get_v_reg(0), 7);
+}
+
+#[test]
+fn skip_key_ignores_the_high_nibble() {
+    // SKP V0; SKNP V0
+    let mut emulator = load(&[0xE0, 0x9E, 0xE0, 0xA1]);
+    emulator.keypress(16, true);
+    emulator.set_v_reg(0, 0x20);
+    emulator.tick();
+    assert_eq!(emulator.get_pc(), 0x202);
+
+    // 0x20 names key 0
+    emulator.keypress(0, true);
+    emulator.set_pc(0x200);
+    emulator.tick();
+    assert_eq!(emulator.get_pc(), 0x204);
+}
+
+#[test]
+fn reports_which_keys_are_down() {
+    let mut emulator = Emulator::new();
+    emulator.keypress(0xA, true);
+    for idx in 0..16 {
+        assert_eq!(emulator.is_key_pressed(idx), idx == 0xA);
+    }
+    emulator.keypress(0xA, false);
+    assert!(!emulator.is_key_pressed(0xA));
+}
+
+#[test]
+fn key_just_pressed_lasts_one_frame() {
+    let mut emulator = load(&[0x12, 0x00]);
+    emulator.keypress(4, true);
+    assert!(emulator.key_just_pressed(4));
+    // Still there once the frame that saw it has run
+    emulator.run_frame();
+    assert!(emulator.key_just_pressed(4));
+    for _ in 0..3 {
+        emulator.run_frame();
+        assert!(!emulator.key_just_pressed(4));
+        assert!(emulator.is_key_pressed(4));
+    }
+
+    emulator.keypress(4, false);
+    emulator.run_frame();
+    emulator.keypress(4, true);
+    assert!(emulator.key_just_pressed(4));
+}
+
+// loop: SKP V0; JP next; V1 += 1; next: JP loop
+const COUNT_KEY_0: [u8; 8] = [0xE0, 0x9E, 0x12, 0x06, 0x71, 0x01, 0x12, 0x00];
+
+#[test]
+fn edge_triggered_keys_only_skip_in_the_first_frame() {
+    let quirks = QuirkConfig { edge_triggered_keys: true, ..QuirkConfig::default() };
+    let mut emulator = Emulator::with_quirks(quirks);
+    emulator.load(&COUNT_KEY_0).unwrap();
+    // One pass round the loop per frame
+    emulator.set_clock_hz(180);
+    emulator.keypress(0, true);
+    for _ in 0..5 {
+        emulator.run_frame();
+    }
+    assert_eq!(emulator.get_v_reg(1), 1);
+
+    // Without the quirk the held key counts every frame
+    let mut emulator = load(&COUNT_KEY_0);
+    emulator.set_clock_hz(180);
+    emulator.keypress(0, true);
+    for _ in 0..5 {
+        emulator.run_frame();
+    }
+    assert_eq!(emulator.get_v_reg(1), 5);
+}
+
+#[test]
+fn keys_can_be_set_from_a_mask() {
+    let mut emulator = Emulator::new();
+    emulator.set_keys(0b1000_0000_0010_0001);
+    assert!(emulator.is_key_pressed(0x0) && emulator.is_key_pressed(0x5) && emulator.is_key_pressed(0xF));
+    assert!(!emulator.is_key_pressed(0x1));
+    assert_eq!(emulator.keys_mask(), 0b1000_0000_0010_0001);
+
+    // Keys missing from the next mask are released
+    emulator.set_keys(0b10);
+    assert_eq!(emulator.keys_mask(), 0b10);
+    assert!(!emulator.is_key_pressed(0x0));
+}
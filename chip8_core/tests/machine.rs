@@ -0,0 +1,334 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use chip8_core::*;
+use common::load;
+
+#[test]
+fn reset_keeps_quirks() {
+    let quirks = QuirkConfig { shift_uses_vy: true, sprite_wrapping: true, ..QuirkConfig::default() };
+    let mut emulator = Emulator::with_quirks(quirks);
+    emulator.reset();
+    assert_eq!(emulator.get_quirks(), quirks);
+}
+
+#[test]
+fn load_rejects_too_large_rom() {
+    let mut emulator = Emulator::new();
+    let rom = [0; 3600];
+    assert_eq!(emulator.load(&rom), Err(LoadError::TooLarge { size: 3600, max: 4096 - 0x200 }));
+}
+
+#[test]
+fn registers_can_be_read_and_written() {
+    let mut emulator = Emulator::new();
+    emulator.set_v_reg(3, 7);
+    emulator.set_pc(0x300);
+    emulator.set_sp(2);
+    assert_eq!(emulator.get_v_reg(3), 7);
+    assert_eq!(emulator.get_pc(), 0x300);
+    assert_eq!(emulator.get_sp(), 2);
+}
+
+#[test]
+#[should_panic]
+fn v_register_past_vf_panics() {
+    Emulator::new().get_v_reg(16);
+}
+
+#[test]
+fn sprite_written_to_ram_reads_back() {
+    let sprite = [0xF0, 0x90, 0xF0, 0x90, 0x90];
+    let mut emulator = Emulator::new();
+    for (offset, &byte) in sprite.iter().enumerate() {
+        emulator.write_mem(0x300 + offset as u16, byte);
+    }
+    assert_eq!(emulator.read_mem_range(0x300, 5), &sprite);
+    assert_eq!(emulator.read_mem(0x301), 0x90);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn loads_rom_from_a_file() {
+    let rom = [0x6A, 0x02, 0x12, 0x00];
+    let path = std::env::temp_dir().join(format!("chip8_core_load_{}.ch8", std::process::id()));
+    std::fs::write(&path, rom).unwrap();
+
+    let mut emulator = Emulator::new();
+    let result = emulator.load_from_path(&path);
+    std::fs::remove_file(&path).unwrap();
+    result.unwrap();
+    assert_eq!(emulator.read_mem_range(0x200, 4), &rom);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn missing_rom_file_is_an_error() {
+    let mut emulator = Emulator::new();
+    assert!(emulator.load_from_path("/nonexistent/rom.ch8").is_err());
+}
+
+#[test]
+fn restore_puts_back_a_snapshot() {
+    // V0 := 5; I := font 0; DRW V0, V0, 5; CALL 0x200
+    let mut emulator = load(&[0x60, 0x05, 0xA0, 0x00, 0xD0, 0x05, 0x22, 0x00]);
+    emulator.set_delay_timer(9);
+    let before = emulator.snapshot();
+    for _ in 0..4 {
+        emulator.tick();
+    }
+    assert_ne!(emulator.snapshot(), before);
+
+    emulator.restore(&before).unwrap();
+    assert_eq!(emulator.snapshot(), before);
+    assert!(emulator.get_display().iter().all(|&pixel| !pixel));
+}
+
+#[test]
+fn programs_can_start_at_0x600() {
+    let rom = [0x6A, 0x02, 0x16, 0x00];
+    let mut emulator = Emulator::with_start_addr(0x600);
+    emulator.load(&rom).unwrap();
+    assert_eq!(emulator.get_pc(), 0x600);
+    assert_eq!(emulator.read_mem_range(0x600, 4), &rom);
+
+    emulator.tick();
+    emulator.reset();
+    assert_eq!(emulator.get_pc(), 0x600);
+}
+
+#[test]
+fn load_at_places_data_anywhere() {
+    let data = [0xDE, 0xAD, 0xBE, 0xEF];
+    let mut emulator = Emulator::new();
+    emulator.load_at(0x300, &data).unwrap();
+    assert_eq!(emulator.read_mem_range(0x300, 4), &data);
+}
+
+#[test]
+fn load_at_checks_bounds() {
+    let mut emulator = Emulator::new();
+    assert_eq!(emulator.load_at(0xFFE, &[1, 2, 3]), Err(LoadError::TooLarge { size: 3, max: 2 }));
+    assert_eq!(emulator.read_mem_range(0xFFE, 2), &[0, 0]);
+
+    // An empty block may sit right at the end of RAM, but not past it
+    assert_eq!(emulator.load_at(0x1000, &[]), Ok(()));
+    assert_eq!(emulator.load_at(0xFFFF, &[]), Err(LoadError::TooLarge { size: 0, max: 0 }));
+}
+
+#[test]
+fn big_ram_holds_programs_past_4kb() {
+    let mut emulator = Emulator::with_ram_size(0x10000);
+    emulator.load(&[0x7A; 8000]).unwrap();
+    // V0 := 0x42; JP 0x3000
+    emulator.load_at(0x3000, &[0x60, 0x42, 0x13, 0x00]).unwrap();
+    emulator.set_pc(0x3000);
+    emulator.tick();
+    assert_eq!(emulator.get_v_reg(0), 0x42);
+    assert_eq!(emulator.read_mem(0x200 + 7999), 0x7A);
+    assert_eq!(emulator.read_mem(0xFFFF), 0);
+}
+
+#[test]
+fn builder_applies_every_option() {
+    let quirks = QuirkConfig { jump_with_offset_vx: true, ..QuirkConfig::default() };
+    let mut emulator = EmulatorBuilder::new()
+        .quirks(quirks)
+        .clock_hz(1000)
+        .ram_size(0x2000)
+        .start_addr(0x1000)
+        .seed(7)
+        .build();
+    assert_eq!(emulator.get_quirks(), quirks);
+    assert_eq!(emulator.get_clock_hz(), 1000);
+    assert_eq!(emulator.load_at(0x1FFF, &[0, 0]), Err(LoadError::TooLarge { size: 2, max: 1 }));
+    assert_eq!(emulator.get_pc(), 0x1000);
+
+    // The seed gives the same rolls as Emulator::with_rng
+    let mut seeded = Emulator::with_rng(7);
+    emulator.load(&[0xC0, 0xFF]).unwrap();
+    seeded.load(&[0xC0, 0xFF]).unwrap();
+    emulator.tick();
+    seeded.tick();
+    assert_eq!(emulator.get_v_reg(0), seeded.get_v_reg(0));
+}
+
+#[test]
+#[should_panic]
+fn builder_rejects_a_start_address_over_the_fonts() {
+    EmulatorBuilder::new().start_addr(0x10).build();
+}
+
+#[test]
+fn validate_rejects_a_bad_pc() {
+    let mut emulator = Emulator::new();
+    assert_eq!(emulator.validate(), Ok(()));
+    emulator.set_pc(0x1000);
+    assert_eq!(emulator.validate(), Err(StateError::PcOutOfRange(0x1000)));
+}
+
+#[test]
+fn restore_rejects_a_bad_stack() {
+    let mut emulator = load(&[0x6A, 0x02]);
+    let mut state = emulator.snapshot();
+    state.sp = 17;
+    assert_eq!(emulator.restore(&state), Err(StateError::SpOutOfRange(17)));
+
+    state.sp = 1;
+    state.stack[0] = 0x2000;
+    assert_eq!(emulator.restore(&state), Err(StateError::ReturnOutOfRange(0x2000)));
+
+    state.stack[0] = 0x202;
+    state.pc = 0x1000;
+    assert_eq!(emulator.restore(&state), Err(StateError::PcOutOfRange(0x1000)));
+
+    // None of which touched the machine
+    assert_eq!(emulator.validate(), Ok(()));
+    emulator.tick();
+    assert_eq!(emulator.get_v_reg(0xA), 2);
+}
+
+#[test]
+fn validate_accepts_any_i() {
+    // I := 0xFFF; V0 := 0x10; I += V0
+    let mut emulator = load(&[0xAF, 0xFF, 0x60, 0x10, 0xF0, 0x1E]);
+    for _ in 0..3 {
+        emulator.tick();
+    }
+    assert_eq!(emulator.get_i(), 0x100F);
+    assert_eq!(emulator.validate(), Ok(()));
+}
+
+#[test]
+fn dumped_ram_loads_back() {
+    let mut emulator = load(&[0x6A, 0x02, 0x12, 0x00]);
+    emulator.write_mem(0x300, 0x55);
+    let image = emulator.dump_ram();
+    assert_eq!(image.len(), 4096);
+
+    let mut other = Emulator::new();
+    other.load_ram(&image).unwrap();
+    assert_eq!(other.dump_ram(), image);
+    assert_eq!(other.load_ram(&image[1..]), Err(LoadError::WrongSize { size: 4095, expected: 4096 }));
+}
+
+#[test]
+fn soft_reset_keeps_the_program() {
+    let rom = [0x60, 0x05, 0xA3, 0x00, 0x22, 0x00];
+    let mut emulator = load(&rom);
+    for _ in 0..3 {
+        emulator.tick();
+    }
+    emulator.soft_reset();
+    assert_eq!(emulator.read_mem_range(0x200, 6), &rom);
+    assert_eq!(emulator.snapshot(), Emulator::new().snapshot());
+}
+
+#[test]
+fn default_is_a_new_emulator() {
+    let emulator = Emulator::default();
+    let fresh = Emulator::new();
+    assert_eq!(emulator.snapshot(), fresh.snapshot());
+    assert_eq!(emulator.dump_ram(), fresh.dump_ram());
+    assert_eq!(emulator.get_quirks(), fresh.get_quirks());
+    assert_eq!(emulator.get_clock_hz(), fresh.get_clock_hz());
+}
+
+#[test]
+fn clones_run_independently() {
+    // loop: ADD V0, 1; JP loop
+    let mut emulator = load(&[0x70, 0x01, 0x12, 0x00]);
+    emulator.tick();
+    let before = emulator.snapshot();
+
+    let mut clone = emulator.clone();
+    for _ in 0..10 {
+        clone.tick();
+    }
+    clone.write_mem(0x300, 0xAA);
+    assert_eq!(clone.get_v_reg(0), 6);
+    assert_eq!(emulator.snapshot(), before);
+    assert_eq!(emulator.read_mem(0x300), 0);
+}
+
+#[test]
+fn empty_rom_is_rejected() {
+    let mut emulator = Emulator::new();
+    assert_eq!(emulator.load(&[]), Err(LoadError::Empty));
+}
+
+// The first bytes out of the 16-bit LFSR (taps 0xB400) seeded with 0xACE1
+const LFSR_BYTES: [u8; 8] = [0xC4, 0x62, 0x3B, 0x0D, 0xBB, 0x77, 0x1F, 0xBF];
+
+#[test]
+fn lfsr_gives_a_fixed_sequence() {
+    let mut rng = LfsrRng::new(0xACE1);
+    let bytes: Vec<u8> = (0..8).map(|_| rng.next_byte()).collect();
+    assert_eq!(bytes, LFSR_BYTES);
+
+    // A zero seed would stick at zero, so it starts from 0xACE1 instead
+    let mut rng = LfsrRng::new(0);
+    assert_eq!(rng.next_byte(), LFSR_BYTES[0]);
+}
+
+#[test]
+fn lfsr_visits_every_nonzero_state() {
+    // Taps 0xB400 give the longest possible cycle, 2^16 - 1 states. That's
+    // odd, so stepping 8 at a time still goes all the way round.
+    let start = LfsrRng::new(0xACE1);
+    let mut rng = start;
+    let mut period = 0;
+    loop {
+        rng.next_byte();
+        period += 1;
+        if rng == start {
+            break;
+        }
+    }
+    assert_eq!(period, 0xFFFF);
+}
+
+#[test]
+fn cxnn_draws_from_the_lfsr() {
+    // VX := rand() & 0xFF for V0-V7
+    let rom: Vec<u8> = (0..8).flat_map(|x| [0xC0 | x, 0xFF]).collect();
+    let mut emulator = Emulator::with_lfsr_rng(0xACE1);
+    emulator.load(&rom).unwrap();
+    for _ in 0..8 {
+        emulator.tick();
+    }
+    let regs: Vec<u8> = (0..8).map(|idx| emulator.get_v_reg(idx)).collect();
+    assert_eq!(regs, LFSR_BYTES);
+
+    let mut built = EmulatorBuilder::new().lfsr_rng().seed(0xACE1).build();
+    built.load(&rom).unwrap();
+    built.tick();
+    assert_eq!(built.get_v_reg(0), LFSR_BYTES[0]);
+}
+
+#[test]
+fn load_segments_places_every_block() {
+    let mut emulator = Emulator::new();
+    let (program, sprites): (&[u8], &[u8]) = (&[0x12, 0x00], &[0xF0, 0x90]);
+    emulator.load_segments(&[(0x200, program), (0x300, sprites)]).unwrap();
+    assert_eq!(emulator.read_mem_range(0x200, 2), program);
+    assert_eq!(emulator.read_mem_range(0x300, 2), sprites);
+}
+
+#[test]
+fn load_segments_rejects_overlaps_without_writing() {
+    let mut emulator = Emulator::new();
+    let result = emulator.load_segments(&[(0x200, &[1, 2, 3, 4]), (0x203, &[5])]);
+    assert_eq!(result, Err(LoadError::Overlap { first: 0x200, second: 0x203 }));
+    assert_eq!(emulator.read_mem_range(0x200, 4), &[0, 0, 0, 0]);
+}
+
+#[test]
+fn load_segments_checks_bounds() {
+    let mut emulator = Emulator::new();
+    let result = emulator.load_segments(&[(0x200, &[1]), (0xFFF, &[1, 2])]);
+    assert_eq!(result, Err(LoadError::TooLarge { size: 2, max: 1 }));
+    assert_eq!(emulator.read_mem(0x200), 0);
+    assert_eq!(emulator.load_segments(&[(0x2000, &[])]), Err(LoadError::TooLarge { size: 0, max: 0 }));
+}
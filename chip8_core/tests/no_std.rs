@@ -0,0 +1,31 @@
+// Only built by `cargo test --no-default-features`, when the core is no_std
+#![cfg(not(feature = "std"))]
+#![allow(clippy::needless_return)]
+
+use chip8_core::*;
+
+#[test]
+fn runs_without_std() {
+    // I := font 0; DRW V0, V0, 5
+    let mut emulator = Emulator::new();
+    emulator.load(&[0xA0, 0x00, 0xD0, 0x05]).unwrap();
+    emulator.tick();
+    emulator.tick();
+    assert!(emulator.get_display()[0]);
+}
+
+#[test]
+fn emulators_share_a_seed_without_std() {
+    // With no OS entropy to seed from, every new emulator rolls the same bytes
+    // VX := rand() for V0-V7
+    let rom: Vec<u8> = (0..8).flat_map(|x| [0xC0 | x, 0xFF]).collect();
+    let roll = || {
+        let mut emulator = Emulator::new();
+        emulator.load(&rom).unwrap();
+        for _ in 0..8 {
+            emulator.tick();
+        }
+        return (0..8).map(|idx| emulator.get_v_reg(idx)).collect::<Vec<u8>>();
+    };
+    assert_eq!(roll(), roll());
+}
@@ -0,0 +1,299 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use chip8_core::*;
+use common::load;
+
+#[test]
+fn call_reports_stack_overflow() {
+    // Each CALL goes to the next one, 17 deep
+    let mut rom = Vec::new();
+    for n in 0..17u16 {
+        let addr = 0x202 + 2 * n;
+        rom.extend_from_slice(&[0x20 | (addr >> 8) as u8, addr as u8]);
+    }
+    let mut emulator = load(&rom);
+    for _ in 0..16 {
+        emulator.tick_checked().unwrap();
+    }
+    assert_eq!(emulator.tick_checked(), Err(ExecError::StackOverflow));
+}
+
+#[test]
+fn ret_reports_stack_underflow() {
+    let mut emulator = load(&[0x00, 0xEE]);
+    assert_eq!(emulator.tick_checked(), Err(ExecError::StackUnderflow));
+}
+
+#[test]
+fn exit_halts_the_program() {
+    // V0 := 1; V1 := 2; EXIT; V2 := 3
+    let mut emulator = load(&[0x60, 0x01, 0x61, 0x02, 0x00, 0xFD, 0x62, 0x03]);
+    for _ in 0..5 {
+        emulator.tick();
+    }
+    assert!(emulator.is_halted());
+    assert_eq!(emulator.get_pc(), 0x206);
+    assert_eq!(emulator.get_v_reg(2), 0);
+    assert_eq!(emulator.step(), Err(ExecError::Halted));
+}
+
+#[test]
+fn fx30_points_i_at_the_big_font() {
+    // V0 := 0xA; LD HF, V0
+    let mut emulator = load(&[0x60, 0x0A, 0xF0, 0x30]);
+    emulator.tick();
+    emulator.tick();
+    // The 10-byte big font follows the 80-byte small one
+    assert_eq!(emulator.get_i(), 80 + 0xA * 10);
+    assert!(emulator.read_mem_range(emulator.get_i(), 10).iter().any(|&row| row != 0));
+}
+
+#[test]
+fn load_registers_moves_i_past_them_by_default() {
+    // I := 0x300; LD V3, [I]
+    let mut emulator = load(&[0xA3, 0x00, 0xF3, 0x65]);
+    emulator.tick();
+    emulator.tick();
+    assert_eq!(emulator.get_i(), 0x304);
+}
+
+#[test]
+fn load_registers_leaves_i_without_quirk() {
+    let quirks = QuirkConfig { load_store_increments_i: false, ..QuirkConfig::default() };
+    let mut emulator = Emulator::with_quirks(quirks);
+    emulator.load(&[0xA3, 0x00, 0xF3, 0x65]).unwrap();
+    emulator.tick();
+    emulator.tick();
+    assert_eq!(emulator.get_i(), 0x300);
+}
+
+// Runs `op` with V1 = 0x02 and V2 = 0x83, returning (V1, VF)
+fn shift(quirks: QuirkConfig, op: u16) -> (u8, u8) {
+    let mut emulator = Emulator::with_quirks(quirks);
+    emulator.load(&op.to_be_bytes()).unwrap();
+    emulator.set_v_reg(1, 0x02);
+    emulator.set_v_reg(2, 0x83);
+    emulator.tick();
+    return (emulator.get_v_reg(1), emulator.get_v_reg(0xF));
+}
+
+#[test]
+fn shifts_use_vx_by_default() {
+    let quirks = QuirkConfig::default();
+    assert_eq!(shift(quirks, 0x8126), (0x01, 0));
+    assert_eq!(shift(quirks, 0x812E), (0x04, 0));
+}
+
+#[test]
+fn shifts_use_vy_with_quirk() {
+    let quirks = QuirkConfig { shift_uses_vy: true, ..QuirkConfig::default() };
+    assert_eq!(shift(quirks, 0x8126), (0x41, 1));
+    assert_eq!(shift(quirks, 0x812E), (0x06, 1));
+}
+
+#[test]
+fn logic_ops_reset_vf_only_with_quirk() {
+    for (logic_resets_vf, expected_vf) in [(true, 0), (false, 1)] {
+        let quirks = QuirkConfig { logic_resets_vf, ..QuirkConfig::default() };
+        for op in [0x8121u16, 0x8122, 0x8123] {
+            let mut emulator = Emulator::with_quirks(quirks);
+            emulator.load(&op.to_be_bytes()).unwrap();
+            emulator.set_v_reg(0xF, 1);
+            emulator.tick();
+            assert_eq!(emulator.get_v_reg(0xF), expected_vf, "{:04X}", op);
+        }
+    }
+}
+
+#[test]
+fn unknown_opcode_is_an_error() {
+    let mut emulator = load(&[0x51, 0x21]);
+    assert_eq!(emulator.tick_checked(), Err(ExecError::UnknownOpcode(0x5121)));
+}
+
+#[test]
+#[should_panic(expected = "0x5121")]
+fn tick_panics_on_unknown_opcode() {
+    load(&[0x51, 0x21]).tick();
+}
+
+#[test]
+fn bcd_is_right_for_every_value() {
+    for vx in 0..=255u8 {
+        // I := 0x300; LD B, V0
+        let mut emulator = load(&[0xA3, 0x00, 0xF0, 0x33]);
+        emulator.set_v_reg(0, vx);
+        emulator.tick();
+        emulator.tick();
+        assert_eq!(emulator.read_mem_range(0x300, 3), &[vx / 100, vx / 10 % 10, vx % 10], "{}", vx);
+    }
+}
+
+#[test]
+fn flags_save_and_restore_registers() {
+    // LD R, V7; then after clobbering, LD V7, R
+    let mut emulator = load(&[0xF7, 0x75, 0xF7, 0x85]);
+    for idx in 0..16 {
+        emulator.set_v_reg(idx, idx as u8 + 1);
+    }
+    emulator.tick();
+    for idx in 0..16 {
+        emulator.set_v_reg(idx, 0);
+    }
+    emulator.tick();
+    for idx in 0..8 {
+        assert_eq!(emulator.get_v_reg(idx), idx as u8 + 1);
+    }
+}
+
+#[test]
+fn flags_stop_at_v7() {
+    // LD R, VF; LD VF, R
+    let mut emulator = load(&[0xFF, 0x75, 0xFF, 0x85]);
+    emulator.set_v_reg(8, 9);
+    emulator.tick();
+    emulator.set_v_reg(8, 0);
+    emulator.tick();
+    assert_eq!(emulator.get_v_reg(8), 0);
+}
+
+#[test]
+fn i_at_the_end_of_ram_wraps() {
+    // I := 0xFFF; DRW V0, V0, 5; V0 := 123; LD B, V0
+    let mut emulator = load(&[0xAF, 0xFF, 0xD0, 0x05, 0x60, 123, 0xF0, 0x33]);
+    for _ in 0..4 {
+        emulator.tick_checked().unwrap();
+    }
+    assert_eq!(emulator.read_mem(0xFFF), 1);
+    assert_eq!(emulator.read_mem_range(0, 2), &[2, 3]);
+}
+
+#[test]
+fn pc_wraps_at_the_top_of_ram() {
+    let mut emulator = Emulator::new();
+    // V0 := 0x42 in the last word of RAM
+    emulator.load_at(0xFFE, &[0x60, 0x42]).unwrap();
+    emulator.set_pc(0xFFE);
+    emulator.tick();
+    assert_eq!(emulator.get_v_reg(0), 0x42);
+    assert_eq!(emulator.get_pc(), 0x000);
+
+    // An opcode in the last byte takes its second byte from address 0,
+    // the top row of the font's 0
+    emulator.write_mem(0xFFF, 0x61);
+    emulator.set_pc(0xFFF);
+    emulator.tick();
+    assert_eq!(emulator.get_v_reg(1), 0xF0);
+    assert_eq!(emulator.get_pc(), 0x001);
+}
+
+#[test]
+fn add_immediate_never_sets_a_carry() {
+    for x in 0..16u8 {
+        for vf in [0, 1, 0xAA] {
+            for (vx, nn, sum) in [(0xFF, 0x01, 0x00), (0xFF, 0xFF, 0xFE), (0x80, 0x80, 0x00), (0x10, 0x01, 0x11)] {
+                let mut emulator = load(&[0x70 | x, nn]);
+                emulator.set_v_reg(0xF, vf);
+                emulator.set_v_reg(x as usize, vx);
+                emulator.tick();
+                assert_eq!(emulator.get_v_reg(x as usize), sum, "V{:X} = {:#04X} + {:#04X}", x, vx, nn);
+
+                // With X = F, VF just holds the sum; no carry overwrites it
+                if x != 0xF {
+                    assert_eq!(emulator.get_v_reg(0xF), vf, "V{:X} = {:#04X} + {:#04X}", x, vx, nn);
+                }
+            }
+        }
+    }
+}
+
+// Runs the 8XYN `op` with V1 and VF set first, returning (V1, VF)
+fn alu_with_vf(op: u16, v1: u8, vf: u8) -> (u8, u8) {
+    let mut emulator = load(&op.to_be_bytes());
+    emulator.set_v_reg(1, v1);
+    emulator.set_v_reg(0xF, vf);
+    emulator.tick();
+    return (emulator.get_v_reg(1), emulator.get_v_reg(0xF));
+}
+
+#[test]
+fn flag_wins_when_vx_is_vf() {
+    // VF += V1
+    assert_eq!(alu_with_vf(0x8F14, 0x02, 0xFF).1, 1);
+    assert_eq!(alu_with_vf(0x8F14, 0x01, 0x10).1, 0);
+    // VF -= V1
+    assert_eq!(alu_with_vf(0x8F15, 0x03, 0x05).1, 1);
+    assert_eq!(alu_with_vf(0x8F15, 0x05, 0x03).1, 0);
+    // VF := V1 - VF
+    assert_eq!(alu_with_vf(0x8F17, 0x05, 0x03).1, 1);
+    assert_eq!(alu_with_vf(0x8F17, 0x03, 0x05).1, 0);
+    // VF += VF
+    assert_eq!(alu_with_vf(0x8FF4, 0x00, 0x80).1, 1);
+}
+
+#[test]
+fn old_vf_is_the_operand_when_vy_is_vf() {
+    // V1 += VF
+    assert_eq!(alu_with_vf(0x81F4, 0xFF, 0x01), (0x00, 1));
+    assert_eq!(alu_with_vf(0x81F4, 0x01, 0x02), (0x03, 0));
+    // V1 -= VF
+    assert_eq!(alu_with_vf(0x81F5, 0x05, 0x03), (0x02, 1));
+    assert_eq!(alu_with_vf(0x81F5, 0x03, 0x05), (0xFE, 0));
+    // V1 := VF - V1
+    assert_eq!(alu_with_vf(0x81F7, 0x03, 0x05), (0x02, 1));
+    assert_eq!(alu_with_vf(0x81F7, 0x05, 0x03), (0xFE, 0));
+}
+
+#[test]
+fn machine_routines_fault_by_default() {
+    let mut emulator = load(&[0x01, 0x23]);
+    assert_eq!(emulator.tick_checked(), Err(ExecError::UnknownOpcode(0x0123)));
+}
+
+#[test]
+fn machine_routines_are_skipped_with_quirk() {
+    let quirks = QuirkConfig { machine_routine_nop: true, ..QuirkConfig::default() };
+    let mut emulator = Emulator::with_quirks(quirks);
+    emulator.load(&[0x01, 0x23]).unwrap();
+    assert_eq!(emulator.tick_checked(), Ok(()));
+    assert_eq!(emulator.get_pc(), 0x202);
+}
+
+#[test]
+fn try_tick_reports_every_fault() {
+    // CALL 0x200, forever
+    let mut emulator = load(&[0x22, 0x00]);
+    for _ in 0..16 {
+        emulator.try_tick().unwrap();
+    }
+    assert_eq!(emulator.try_tick(), Err(Fault::StackOverflow));
+
+    assert_eq!(load(&[0x00, 0xEE]).try_tick(), Err(Fault::StackUnderflow));
+    assert_eq!(load(&[0xFF, 0xFF]).try_tick(), Err(Fault::UnknownOpcode(0xFFFF)));
+
+    let mut emulator = load(&[0x00, 0xFD]);
+    emulator.try_tick().unwrap();
+    assert_eq!(emulator.try_tick(), Err(Fault::Halted));
+
+    let mut emulator = load(&[0x12, 0x00]);
+    emulator.add_breakpoint(0x200);
+    assert_eq!(emulator.try_tick(), Err(Fault::Breakpoint(0x200)));
+}
+
+#[test]
+fn f000_loads_a_16_bit_address_into_i() {
+    let mut emulator = load(&[0xF0, 0x00, 0x12, 0x34]);
+    emulator.tick();
+    assert_eq!(emulator.get_i(), 0x1234);
+    assert_eq!(emulator.get_pc(), 0x204);
+}
+
+#[test]
+fn skips_step_over_all_of_f000() {
+    // SE V0, 0; LD I, LONG 0x1234; V1 := 1
+    let mut emulator = load(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x61, 0x01]);
+    emulator.tick();
+    assert_eq!(emulator.get_pc(), 0x206);
+}
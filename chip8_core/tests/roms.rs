@@ -0,0 +1,46 @@
+// Runs the ROMs in cartridges/ headless and checks the screen each ends up
+// on against a golden bitmap in tests/golden, to catch opcode and quirk
+// regressions. A golden is the packed display, one row per line, with '#'
+// for each lit pixel. The ROMs here don't use CXNN, so the RNG doesn't
+// matter. Test-suite ROMs such as Timendus's corax+ aren't in the repo;
+// each only needs its file in cartridges/ and a line in ROMS.
+#![allow(clippy::needless_return)]
+
+use chip8_core::*;
+
+// (file in cartridges/, frames to run, golden in tests/golden)
+const ROMS: [(&str, usize, &str); 4] = [
+    ("chip8logo.chp", 60, include_str!("golden/chip8logo.txt")),
+    ("INVADERS", 120, include_str!("golden/invaders.txt")),
+    ("TANK", 60, include_str!("golden/tank.txt")),
+    ("lunar_lander.chp", 60, include_str!("golden/lunar_lander.txt")),
+];
+
+fn packed_bitmap(emulator: &Emulator) -> String {
+    let (width, _) = emulator.display_dimensions();
+    let packed = emulator.get_display_packed();
+    let mut bitmap = String::new();
+    for row in packed.chunks(width / 8) {
+        for byte in row {
+            for bit in 0..8 {
+                bitmap.push(if byte & (0x80 >> bit) != 0 { '#' } else { '.' });
+            }
+        }
+        bitmap.push('\n');
+    }
+    return bitmap;
+}
+
+#[test]
+fn roms_reach_their_golden_screens() {
+    for (file, frames, golden) in ROMS {
+        let rom = std::fs::read(format!("{}/../cartridges/{}", env!("CARGO_MANIFEST_DIR"), file)).unwrap();
+        let mut emulator = Emulator::new();
+        emulator.load(&rom).unwrap();
+        for _ in 0..frames {
+            emulator.run_frame();
+        }
+        let bitmap = packed_bitmap(&emulator);
+        assert!(bitmap == golden, "{} after {} frames:\n{}", file, frames, bitmap);
+    }
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "serde")]
+
+use chip8_core::*;
+
+#[test]
+fn save_state_round_trips_through_json() {
+    let mut emulator = Emulator::new();
+    emulator.load(include_bytes!("../../cartridges/INVADERS")).unwrap();
+    for _ in 0..500 {
+        emulator.tick();
+    }
+
+    let json = serde_json::to_string(&emulator).unwrap();
+    let restored: Emulator = serde_json::from_str(&json).unwrap();
+    assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+    assert_eq!(restored.get_display(), emulator.get_display());
+}
+
+#[test]
+fn corrupt_stack_pointer_faults_instead_of_panicking() {
+    // RET
+    let mut emulator = Emulator::new();
+    emulator.load(&[0x00, 0xEE]).unwrap();
+    let mut json = serde_json::to_value(&emulator).unwrap();
+    json["sp"] = 17.into();
+    let mut restored: Emulator = serde_json::from_value(json).unwrap();
+
+    assert_eq!(restored.validate(), Err(StateError::SpOutOfRange(17)));
+    assert_eq!(restored.tick_checked(), Err(ExecError::StackOverflow));
+}
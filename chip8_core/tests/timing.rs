@@ -0,0 +1,375 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use chip8_core::*;
+use common::load;
+
+#[test]
+fn beeps_while_the_sound_timer_runs() {
+    // V0 := 3; LD ST, V0
+    let mut emulator = load(&[0x60, 0x03, 0xF0, 0x18]);
+    emulator.tick();
+    assert!(!emulator.is_beeping());
+    emulator.tick();
+    for _ in 0..3 {
+        assert!(emulator.is_beeping());
+        emulator.tick_timers();
+    }
+    assert!(!emulator.is_beeping());
+}
+
+#[test]
+fn beep_is_a_square_wave() {
+    // V0 := 10; LD ST, V0; V0 := 0; LD ST, V0
+    let mut emulator = load(&[0x60, 10, 0xF0, 0x18, 0x60, 0, 0xF0, 0x18]);
+    emulator.tick();
+    emulator.tick();
+    let mut buf = [0.0; 441];
+    emulator.fill_audio(&mut buf, 44100);
+    assert!(buf.iter().any(|&sample| sample > 0.0));
+    assert!(buf.iter().any(|&sample| sample < 0.0));
+    assert!(buf.iter().all(|&sample| sample.abs() == buf[0].abs()));
+
+    // And silence once the timer runs out
+    emulator.tick();
+    emulator.tick();
+    emulator.fill_audio(&mut buf, 44100);
+    assert!(buf.iter().all(|&sample| sample == 0.0));
+}
+
+#[test]
+fn run_frame_runs_clock_hz_over_60_ticks() {
+    // V0 := 5; LD DT, V0; then V1 += 1 in a loop
+    let mut emulator = load(&[0x60, 0x05, 0xF0, 0x15, 0x71, 0x01, 0x12, 0x04]);
+    emulator.set_clock_hz(600);
+    emulator.run_frame();
+    // 2 setup instructions, then 4 times round the 2 instruction loop
+    assert_eq!(emulator.get_v_reg(1), 4);
+    assert_eq!(emulator.get_delay_timer(), 4);
+}
+
+#[test]
+fn rewind_goes_back_to_an_earlier_frame() {
+    // I := font 0; loop: DRW V0, V0, 5; ADD V0, 1; JP loop
+    let mut emulator = load(&[0xA0, 0x00, 0xD0, 0x05, 0x70, 0x01, 0x12, 0x02]);
+    emulator.set_clock_hz(180);
+    emulator.enable_rewind(10);
+
+    let mut earlier = None;
+    for frame in 0..10 {
+        if frame == 7 {
+            earlier = Some((emulator.get_pc(), emulator.get_v_reg(0), emulator.get_display().to_vec()));
+        }
+        emulator.run_frame();
+    }
+    for _ in 0..3 {
+        assert!(emulator.rewind());
+    }
+    assert_eq!(Some((emulator.get_pc(), emulator.get_v_reg(0), emulator.get_display().to_vec())), earlier);
+    assert_eq!(emulator.frame_count(), 7);
+}
+
+#[test]
+fn rewind_puts_back_counters_and_audio() {
+    // What the frontend does each frame besides run_frame
+    fn frame(emulator: &mut Emulator) -> Vec<f32> {
+        emulator.run_frame();
+        emulator.tick_timers_dt(7);
+        let mut buf = vec![0.0; 100];
+        emulator.fill_audio(&mut buf, 44100);
+        return buf;
+    }
+
+    let mut emulator = load(&DRAW_LOOP);
+    emulator.set_clock_hz(190);
+    emulator.set_sound_timer(200);
+    emulator.enable_rewind(10);
+    for n in 0..7 {
+        emulator.keypress(3, n == 5);
+        frame(&mut emulator);
+    }
+    let mut earlier = emulator.clone();
+    for _ in 0..3 {
+        frame(&mut emulator);
+    }
+    for _ in 0..3 {
+        assert!(emulator.rewind());
+    }
+
+    assert_eq!(emulator.cycle_count(), earlier.cycle_count());
+    assert_eq!(emulator.opcode_histogram(), earlier.opcode_histogram());
+    assert_eq!(emulator.last_draw_bounds(), earlier.last_draw_bounds());
+    // From here on the two run exactly alike
+    for _ in 0..3 {
+        assert_eq!(frame(&mut emulator), frame(&mut earlier));
+        assert_eq!(emulator.key_just_pressed(3), earlier.key_just_pressed(3));
+        assert_eq!(emulator.get_delay_timer(), earlier.get_delay_timer());
+        assert_eq!(emulator.get_display(), earlier.get_display());
+    }
+}
+
+#[test]
+fn counts_executed_cycles() {
+    let mut emulator = load(&[0x12, 0x00]);
+    for _ in 0..100 {
+        emulator.tick();
+    }
+    assert_eq!(emulator.cycle_count(), 100);
+}
+
+#[test]
+fn jump_to_self_is_idle() {
+    let mut emulator = load(&[0x12, 0x00]);
+    assert!(!emulator.is_idle());
+    emulator.tick();
+    assert!(emulator.is_idle());
+}
+
+#[test]
+fn jump_elsewhere_is_not_idle() {
+    // JP 0x202; JP 0x200
+    let mut emulator = load(&[0x12, 0x02, 0x12, 0x00]);
+    emulator.tick();
+    emulator.tick();
+    assert!(!emulator.is_idle());
+}
+
+#[test]
+fn rewind_restores_a_key_wait_in_progress() {
+    // LD V0, K; JP 0x202
+    let mut emulator = load(&[0xF0, 0x0A, 0x12, 0x02]);
+    emulator.set_clock_hz(60);
+    emulator.enable_rewind(10);
+    emulator.keypress(5, true);
+    emulator.run_frame();
+    emulator.keypress(5, false);
+    emulator.run_frame();
+    assert_eq!(emulator.get_v_reg(0), 5);
+
+    // Back to before the release, the wait still knows key 5 went down
+    assert!(emulator.rewind());
+    assert_eq!(emulator.get_v_reg(0), 0);
+    emulator.run_frame();
+    assert_eq!(emulator.get_v_reg(0), 5);
+}
+
+#[test]
+fn f002_loads_the_audio_pattern() {
+    let pattern: Vec<u8> = (0..16).map(|n| n * 0x11).collect();
+    // I := 0x206; AUDIO; JP 0x204; then the pattern
+    let mut rom = vec![0xA2, 0x06, 0xF0, 0x02, 0x12, 0x04];
+    rom.extend_from_slice(&pattern);
+    let mut emulator = load(&rom);
+    emulator.tick();
+    emulator.tick();
+    assert_eq!(&emulator.get_audio_pattern()[..], &pattern[..]);
+}
+
+#[test]
+fn pattern_plays_at_low_sample_rates() {
+    // I := 0x20C; AUDIO; V0 := 255; PITCH V0; V1 := 10; LD ST, V1; then the pattern
+    let mut rom = vec![0xA2, 0x0C, 0xF0, 0x02, 0x60, 0xFF, 0xF0, 0x3A, 0x61, 10, 0xF1, 0x18];
+    rom.extend_from_slice(&[0xF0; 16]);
+    let mut emulator = load(&rom);
+    for _ in 0..6 {
+        emulator.tick();
+    }
+
+    // At pitch 255 each sample skips several times round the pattern
+    let mut buf = [0.0; 300];
+    emulator.fill_audio(&mut buf, 300);
+    assert!(buf.iter().all(|&sample| sample != 0.0));
+
+    // And there's nothing to play at a sample rate of 0
+    emulator.fill_audio(&mut buf, 0);
+    assert!(buf.iter().all(|&sample| sample == 0.0));
+}
+
+#[test]
+fn timers_can_be_set_and_read() {
+    let mut emulator = Emulator::new();
+    emulator.set_delay_timer(5);
+    emulator.set_sound_timer(7);
+    emulator.tick_timers();
+    emulator.tick_timers();
+    assert_eq!(emulator.get_delay_timer(), 3);
+    assert_eq!(emulator.get_sound_timer(), 5);
+}
+
+#[test]
+fn paused_emulator_stands_still() {
+    // SKP V0; JP 0x200
+    let mut emulator = load(&[0xE0, 0x9E, 0x12, 0x00]);
+    emulator.set_delay_timer(5);
+    emulator.set_paused(true);
+    for _ in 0..5 {
+        emulator.tick();
+        emulator.run_frame();
+        emulator.tick_timers();
+    }
+    assert_eq!(emulator.get_pc(), 0x200);
+    assert_eq!(emulator.get_delay_timer(), 5);
+
+    // Keys still register while paused
+    emulator.keypress(0, true);
+    emulator.set_paused(false);
+    emulator.tick();
+    assert_eq!(emulator.get_pc(), 0x204);
+}
+
+// I := font 0; loop: DRW V0, V0, 5; ADD V0, 1; JP loop
+const DRAW_LOOP: [u8; 8] = [0xA0, 0x00, 0xD0, 0x05, 0x70, 0x01, 0x12, 0x02];
+
+#[test]
+fn display_wait_allows_one_draw_per_frame() {
+    let quirks = QuirkConfig { display_wait: true, ..QuirkConfig::default() };
+    let mut emulator = Emulator::with_quirks(quirks);
+    emulator.load(&DRAW_LOOP).unwrap();
+    for frame in 1..=3 {
+        emulator.run_frame();
+        assert_eq!(emulator.opcode_histogram()[0xD], frame);
+    }
+}
+
+#[test]
+fn draw_between_frames_does_not_cut_the_next_short() {
+    let quirks = QuirkConfig { display_wait: true, ..QuirkConfig::default() };
+    let mut emulator = Emulator::with_quirks(quirks);
+    emulator.load(&DRAW_LOOP).unwrap();
+    emulator.set_clock_hz(600);
+    emulator.tick();
+    emulator.tick();
+
+    // ADD, JP, then the frame's own DRAW
+    emulator.run_frame();
+    assert_eq!(emulator.cycle_count(), 5);
+    for _ in 0..3 {
+        emulator.tick();
+    }
+    emulator.run_frame();
+    assert_eq!(emulator.cycle_count(), 11);
+}
+
+#[test]
+fn display_wait_is_off_by_default() {
+    assert!(!QuirkConfig::default().display_wait);
+    let mut emulator = load(&DRAW_LOOP);
+    emulator.run_frame();
+    assert!(emulator.opcode_histogram()[0xD] > 1);
+}
+
+#[test]
+fn run_until_stops_when_the_predicate_holds() {
+    // loop: ADD V0, 1; JP loop
+    let mut emulator = load(&[0x70, 0x01, 0x12, 0x00]);
+    assert!(emulator.run_until(100, |emulator| emulator.get_v_reg(0) == 10));
+    assert_eq!(emulator.get_v_reg(0), 10);
+    assert_eq!(emulator.cycle_count(), 19);
+}
+
+#[test]
+fn run_until_gives_up_after_max_cycles() {
+    let mut emulator = load(&[0x70, 0x01, 0x12, 0x00]);
+    assert!(!emulator.run_until(100, |emulator| emulator.get_v_reg(0) == 200));
+    assert_eq!(emulator.cycle_count(), 100);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn timed_frame_runs_a_frame_in_a_60th_of_a_second() {
+    let mut emulator = load(&[0x12, 0x00]);
+    emulator.set_clock_hz(600);
+    let start = std::time::Instant::now();
+    emulator.run_frame_timed();
+    assert!(start.elapsed() >= std::time::Duration::from_millis(16));
+    assert_eq!(emulator.cycle_count(), 10);
+    assert_eq!(emulator.frame_count(), 1);
+}
+
+#[test]
+fn counts_frames_run() {
+    let mut emulator = load(&[0x12, 0x00]);
+    emulator.enable_rewind(5);
+    for _ in 0..30 {
+        emulator.run_frame();
+    }
+    assert_eq!(emulator.frame_count(), 30);
+    emulator.rewind();
+    assert_eq!(emulator.frame_count(), 29);
+}
+
+#[test]
+fn uneven_clock_rates_come_out_right_over_a_second() {
+    let mut emulator = load(&[0x12, 0x00]);
+    emulator.set_clock_hz(1000);
+    emulator.set_delay_timer(60);
+    for _ in 0..59 {
+        emulator.run_frame();
+    }
+    assert_eq!(emulator.get_delay_timer(), 1);
+    emulator.run_frame();
+    assert_eq!(emulator.get_delay_timer(), 0);
+    assert_eq!(emulator.cycle_count(), 1000);
+}
+
+#[test]
+fn timers_follow_elapsed_time() {
+    let mut emulator = Emulator::new();
+    emulator.set_sound_timer(60);
+    // 60 frames of about 16.67ms, one second in all
+    let chunks: Vec<u32> = [17, 17, 16].iter().cycle().take(60).copied().collect();
+    for &dt in &chunks[..59] {
+        emulator.tick_timers_dt(dt);
+    }
+    assert!(emulator.is_beeping());
+    emulator.tick_timers_dt(chunks[59]);
+    assert!(!emulator.is_beeping());
+}
+
+#[test]
+fn short_chunks_carry_over() {
+    let mut emulator = Emulator::new();
+    emulator.set_delay_timer(10);
+    for _ in 0..5 {
+        emulator.tick_timers_dt(10);
+    }
+    // 50ms is three 60Hz ticks
+    assert_eq!(emulator.get_delay_timer(), 7);
+}
+
+#[test]
+fn run_frames_matches_run_frame() {
+    let mut warped = load(&DRAW_LOOP);
+    let mut stepped = load(&DRAW_LOOP);
+    warped.set_delay_timer(200);
+    stepped.set_delay_timer(200);
+
+    warped.run_frames(100).unwrap();
+    for _ in 0..100 {
+        stepped.run_frame();
+    }
+    assert_eq!(warped.snapshot(), stepped.snapshot());
+    assert_eq!(warped.cycle_count(), stepped.cycle_count());
+    assert_eq!(warped.frame_count(), 100);
+}
+
+#[test]
+fn run_frames_finishes_a_frame_it_stopped_in() {
+    let mut straight = load(&DRAW_LOOP);
+    straight.set_delay_timer(10);
+    straight.run_frames(2).unwrap();
+
+    // Stopped by a breakpoint three instructions into the first frame
+    let mut interrupted = load(&DRAW_LOOP);
+    interrupted.set_delay_timer(10);
+    interrupted.add_breakpoint(0x204);
+    assert_eq!(interrupted.run_frames(2), Err(ExecError::Breakpoint(0x204)));
+    assert_eq!(interrupted.frame_count(), 0);
+    interrupted.remove_breakpoint(0x204);
+    interrupted.run_frames(2).unwrap();
+
+    assert_eq!(interrupted.snapshot(), straight.snapshot());
+    assert_eq!(interrupted.cycle_count(), straight.cycle_count());
+    assert_eq!(interrupted.frame_count(), 2);
+}
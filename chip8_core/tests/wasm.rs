@@ -0,0 +1,23 @@
+#![cfg(feature = "wasm")]
+
+use chip8_core::*;
+
+#[test]
+fn display_bytes_match_the_screen() {
+    // V0 := 3; I := font 8; DRW V0, V0, 5
+    let rom = [0x60, 0x03, 0xA0, 0x28, 0xD0, 0x05];
+    let mut wasm = WasmEmulator::new();
+    let mut emulator = Emulator::new();
+    wasm.load(&rom).unwrap();
+    emulator.load(&rom).unwrap();
+    for _ in 0..3 {
+        wasm.tick();
+        emulator.tick();
+    }
+
+    let bytes = wasm.display_bytes();
+    assert_eq!((wasm.display_width(), wasm.display_height()), (64, 32));
+    for (idx, &pixel) in emulator.get_display().iter().enumerate() {
+        assert_eq!(bytes[idx / 8] & (0x80 >> (idx % 8)) != 0, pixel, "pixel {}", idx);
+    }
+}
@@ -2,17 +2,16 @@ use chip8_core::*;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
 use std::env;
+use std::fs;
 use sdl2::event::Event;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::keyboard::Keycode;
-use std::fs::File; 
-use std::io::Read;
 
-const SCALE: u32 = 15;
+// Even, so 128x64 hi-res fills the window exactly at half the scale
+const SCALE: u32 = 16;
 const WINDOW_WIDTH: u32 = (SCREEN_WIDTH as u32) * SCALE;
 const WINDOW_HEIGHT: u32 = (SCREEN_HEIGHT as u32) * SCALE;
-const TICKS_PER_FRAME: usize = 10;
 
 fn main() {
     let args: Vec<_> = env::args().collect();
@@ -37,11 +36,9 @@ fn main() {
     let mut event_pump = sdl_context.event_pump().unwrap();
     let mut chip8 = Emulator:: new();
 
-    let mut rom = File::open(&args[1]).expect("Unable to open file");
-    let mut buffer = Vec::new();
-
-    rom.read_to_end(&mut buffer).unwrap();
-    chip8.load(&buffer);
+    // Kept around so Backspace can restart the game without touching the file again
+    let rom = fs::read(&args[1]).expect("Unable to read ROM");
+    chip8.load(&rom).expect("Unable to load ROM");
 
     'gameloop: loop {
         for event in event_pump.poll_iter() {
@@ -50,8 +47,9 @@ fn main() {
                     break 'gameloop;
                 },
                 Event::KeyDown{keycode: Some(Keycode::Backspace), ..} => {
+                    // The same bytes loaded fine above, so this can't fail
                     chip8.reset();
-                    chip8.load(&buffer);
+                    chip8.load(&rom).unwrap();
                 }
                 Event::KeyDown{keycode: Some(key), ..} => {
                     if let Some(k) = key2btn(key) {
@@ -66,10 +64,7 @@ fn main() {
                 _ => ()
             }
         }
-        for _ in 0..TICKS_PER_FRAME {
-            chip8.tick();
-        }
-        chip8.tick_timers();
+        chip8.run_frame();
         draw_screen(&chip8, &mut canvas);
     }
 }
@@ -79,18 +74,17 @@ fn draw_screen(emulator: &Emulator, canvas: &mut Canvas<Window>) {
     canvas.set_draw_color(Color::RGB(0, 0, 0));
     canvas.clear(); 
 
-    let screen_buf = emulator.get_display(); 
-    // Set draw colour to white, and iterate through each point 
+    // Hi-res screens are drawn at a smaller scale into the same window
+    let (width, _) = emulator.display_dimensions();
+    let scale = WINDOW_WIDTH / (width as u32);
+    // Set draw colour to white, and iterate through each lit point 
     canvas.set_draw_color(Color::RGB(255, 255, 255));
-    for (idx, pixel) in screen_buf.iter().enumerate() {
-        if *pixel {
-            let x = (idx % SCREEN_WIDTH) as u32;
-            let y = (idx / SCREEN_WIDTH) as u32;
+    for (x, y) in emulator.lit_pixels() {
+        let (x, y) = (x as u32, y as u32);
 
-            // Draw rectangle at (x, y), scaled up
-            let rect = Rect::new((x * SCALE) as i32, (y * SCALE) as i32, SCALE, SCALE);
-            canvas.fill_rect(rect).unwrap();
-        }
+        // Draw rectangle at (x, y), scaled up
+        let rect = Rect::new((x * scale) as i32, (y * scale) as i32, scale, scale);
+        canvas.fill_rect(rect).unwrap();
     }
     canvas.present();
 }
@@ -47,24 +47,24 @@ impl EmuWasm {
     }
 
     #[wasm_bindgen]
-    pub fn load_game(&mut self, data: Uint8Array) {
-        self.chip8.load(&data.to_vec());
+    pub fn load_game(&mut self, data: Uint8Array) -> Result<(), JsValue> {
+        return self.chip8.load(&data.to_vec())
+            .map_err(|e| JsValue::from_str(&e.to_string()));
     }
 
     #[wasm_bindgen]
     pub fn draw_screen(&mut self, scale: usize) {
-        let disp = self.chip8.get_display();
-        for i in 0..(SCREEN_WIDTH * SCREEN_HEIGHT) {
-            if disp[i] {
-                let x = i % SCREEN_WIDTH;
-                let y = i / SCREEN_WIDTH;
-                self.ctx.fill_rect(
-                    (x * scale) as f64,
-                    (y * scale) as f64,
-                    scale as f64,
-                    scale as f64
-                );
-            }
+        // `scale` is given for the low res screen, hi-res pixels are smaller.
+        // Fractional, so an odd scale still fills the canvas in hi-res.
+        let (width, _) = self.chip8.display_dimensions();
+        let scale = (scale * SCREEN_WIDTH) as f64 / width as f64;
+        for (x, y) in self.chip8.lit_pixels() {
+            self.ctx.fill_rect(
+                x as f64 * scale,
+                y as f64 * scale,
+                scale,
+                scale
+            );
         }
     }
 